@@ -0,0 +1,273 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{ACHIEVEMENTS_FILE, ACHIEVEMENT_TOAST_TIME},
+    difficulty::Difficulty,
+    match_settings::MatchSettings,
+    pause::MatchState,
+    plot::Crop,
+    replay::ReplayMode,
+    stats::MatchStats,
+    utils::save_dir,
+    waves::WaveDirector,
+    GameState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Achievement {
+    FirstHarvest,
+    CloverPatch,
+    NothingWasted,
+    Untouched,
+    Exterminator,
+    Landlord,
+    GardenKeeper,
+    Nightmare,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 8] = [
+        Achievement::FirstHarvest,
+        Achievement::CloverPatch,
+        Achievement::NothingWasted,
+        Achievement::Untouched,
+        Achievement::Exterminator,
+        Achievement::Landlord,
+        Achievement::GardenKeeper,
+        Achievement::Nightmare,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Achievement::FirstHarvest => "First Harvest",
+            Achievement::CloverPatch => "Clover Patch",
+            Achievement::NothingWasted => "Nothing Wasted",
+            Achievement::Untouched => "Untouched",
+            Achievement::Exterminator => "Exterminator",
+            Achievement::Landlord => "Landlord",
+            Achievement::GardenKeeper => "Garden Keeper",
+            Achievement::Nightmare => "Sweet Dreams",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Achievement::FirstHarvest => "Harvest a crop",
+            Achievement::CloverPatch => "Survive 10 minutes planting only clover",
+            Achievement::NothingWasted => "Win without letting a crop decay",
+            Achievement::Untouched => "Win without the rose taking damage",
+            Achievement::Exterminator => "Kill 100 pests in one game",
+            Achievement::Landlord => "Unlock 5 plots in one game",
+            Achievement::GardenKeeper => "Win a game",
+            Achievement::Nightmare => "Win a game on Nightmare",
+        }
+    }
+
+    fn earned(&self, run: &Run) -> bool {
+        let stats = run.stats;
+        match self {
+            Achievement::FirstHarvest => !stats.harvested.is_empty(),
+            Achievement::CloverPatch => {
+                run.seconds >= 600
+                    && stats.planted.contains_key(&Crop::Clover)
+                    && stats.planted.keys().all(|crop| crop == &Crop::Clover)
+            }
+            Achievement::NothingWasted => run.won && stats.decayed.is_empty(),
+            Achievement::Untouched => run.won && stats.rose_damage == 0.0,
+            Achievement::Exterminator => stats.total_kills() >= 100,
+            Achievement::Landlord => stats.plots_unlocked >= 5,
+            Achievement::GardenKeeper => run.won,
+            Achievement::Nightmare => run.won && run.difficulty == Difficulty::Nightmare,
+        }
+    }
+}
+
+// What the conditions get to look at
+struct Run<'a> {
+    stats: &'a MatchStats,
+    seconds: u64,
+    difficulty: Difficulty,
+    won: bool,
+}
+
+// Unlocked for good, kept next to the saves
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Achievements {
+    pub unlocked: HashSet<Achievement>,
+}
+
+impl Achievements {
+    fn load() -> Self {
+        let path = save_dir().join(ACHIEVEMENTS_FILE);
+        let Ok(text) = std::fs::read_to_string(&path) else { return Achievements::default() };
+
+        ron::from_str(&text).unwrap_or_else(|err| {
+            warn!("Couldn't parse achievements {:?}: {}", path, err);
+            Achievements::default()
+        })
+    }
+
+    fn write(&self) {
+        let path = save_dir().join(ACHIEVEMENTS_FILE);
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                std::fs::create_dir_all(save_dir())
+                    .and_then(|_| std::fs::write(&path, text))
+                    .map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            warn!("Couldn't save achievements to {:?}: {}", path, err);
+        }
+    }
+}
+
+pub fn spawn_achievement_list(
+    parent: &mut ChildBuilder,
+    assets: &AssetServer,
+    achievements: &Achievements,
+) {
+    for achievement in Achievement::ALL {
+        let color = if achievements.unlocked.contains(&achievement) {
+            Color::YELLOW
+        } else {
+            Color::GRAY
+        };
+
+        parent.spawn(TextBundle::from_section(
+            format!("{:<16}{}", achievement.name(), achievement.description()),
+            TextStyle {
+                font: assets.load("fonts/ModeSeven.ttf"),
+                font_size: 20.0,
+                color,
+            },
+        ));
+    }
+}
+
+pub struct Unlocked(pub Achievement);
+
+#[derive(Component)]
+pub struct Toast(Timer);
+
+pub struct Plugin;
+
+impl Plugin {
+    fn check(
+        mut unlocked: EventWriter<Unlocked>,
+        mut achievements: ResMut<Achievements>,
+        stats: Res<MatchStats>,
+        director: Res<WaveDirector>,
+        (difficulty, settings): (Res<Difficulty>, Res<MatchSettings>),
+        state: Res<CurrentState<MatchState>>,
+        mode: Option<Res<ReplayMode>>,
+    ) {
+        // Custom games can make any of them trivial
+        if mode.is_some_and(|mode| mode.is_playback()) || !settings.is_default() {
+            return;
+        }
+
+        let run = Run {
+            stats: &stats,
+            seconds: director.total.as_secs(),
+            difficulty: *difficulty,
+            won: state.0 == MatchState::Victory,
+        };
+
+        let earned = Achievement::ALL
+            .into_iter()
+            .filter(|achievement| !achievements.unlocked.contains(achievement))
+            .filter(|achievement| achievement.earned(&run))
+            .collect::<Vec<_>>();
+
+        if earned.is_empty() {
+            return;
+        }
+
+        for achievement in earned {
+            achievements.unlocked.insert(achievement);
+            unlocked.send(Unlocked(achievement));
+        }
+        achievements.write();
+    }
+
+    fn spawn_toasts(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        mut unlocked: EventReader<Unlocked>,
+        q_toast: Query<(), With<Toast>>,
+    ) {
+        let shown = q_toast.iter().count();
+
+        for (i, Unlocked(achievement)) in unlocked.iter().enumerate() {
+            Self::spawn_toast(&mut cmd, &assets, *achievement, shown + i);
+        }
+    }
+
+    fn spawn_toast(cmd: &mut Commands, assets: &AssetServer, achievement: Achievement, i: usize) {
+        cmd.spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        right: Val::Px(10.0),
+                        top: Val::Px(10.0 + 60.0 * i as f32),
+                        ..default()
+                    },
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                z_index: ZIndex::Global(20),
+                ..default()
+            },
+            Toast(Timer::from_seconds(ACHIEVEMENT_TOAST_TIME, TimerMode::Once)),
+        ))
+        .with_children(|toast| {
+            toast.spawn(TextBundle::from_section(
+                format!("Achievement: {}", achievement.name()),
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 20.0,
+                    color: Color::YELLOW,
+                },
+            ));
+            toast.spawn(TextBundle::from_section(
+                achievement.description(),
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+    }
+
+    // Toasts run on wall time, so they read the same at any game speed or while paused
+    fn update_toasts(mut cmd: Commands, time: Res<Time>, mut q_toast: Query<(Entity, &mut Toast)>) {
+        for (entity, mut toast) in &mut q_toast {
+            toast.0.tick(time.raw_delta());
+
+            if toast.0.finished() {
+                cmd.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Achievements::load())
+            .add_event::<Unlocked>()
+            .add_system(Self::check.run_in_state(GameState::InGame))
+            .add_system(Self::spawn_toasts.run_in_state(GameState::InGame))
+            .add_system(Self::update_toasts.run_in_state(GameState::InGame));
+    }
+}
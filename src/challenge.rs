@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{CHALLENGE_FILE, CHALLENGE_MODIFIERS, LEVELS},
+    difficulty::Difficulty,
+    enemy::EnemyKind,
+    game::Compost,
+    level::LevelChoice,
+    pause::MatchState,
+    plot::Crop,
+    replay::ReplayMode,
+    rng::{GameRng, SeedSetting},
+    scenario::ScenarioChoice,
+    scores::Score,
+    stats::MatchStats,
+    utils::{save_dir, unix_seconds},
+    waves::WaveDirector,
+    GameState,
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Modifier {
+    NoWheat,
+    NoClover,
+    DoubleAphids,
+    DoubleCaterpillars,
+    HalfCompost,
+}
+
+impl Modifier {
+    pub const ALL: [Modifier; 5] = [
+        Modifier::NoWheat,
+        Modifier::NoClover,
+        Modifier::DoubleAphids,
+        Modifier::DoubleCaterpillars,
+        Modifier::HalfCompost,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Modifier::NoWheat => "No wheat",
+            Modifier::NoClover => "No clover",
+            Modifier::DoubleAphids => "Double aphids",
+            Modifier::DoubleCaterpillars => "Double caterpillars",
+            Modifier::HalfCompost => "Half starting compost",
+        }
+    }
+}
+
+// Everything about a day's run that has to match between players
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Challenge {
+    // Days since the Unix epoch, in UTC so everyone rolls over at the same moment
+    pub day: u64,
+    pub seed: u64,
+    pub level: String,
+    pub modifiers: Vec<Modifier>,
+}
+
+impl Challenge {
+    pub fn today() -> Self {
+        Self::for_day(unix_seconds() / SECONDS_PER_DAY)
+    }
+
+    fn for_day(day: u64) -> Self {
+        // Spread consecutive days apart so their seeds don't start out alike
+        let seed = fastrand::Rng::with_seed(day).u64(..);
+        let rng = fastrand::Rng::with_seed(seed);
+
+        let mut modifiers = Modifier::ALL.to_vec();
+        rng.shuffle(&mut modifiers);
+        modifiers.truncate(CHALLENGE_MODIFIERS);
+
+        Challenge {
+            day,
+            seed,
+            level: LEVELS[rng.usize(..LEVELS.len())].to_string(),
+            modifiers,
+        }
+    }
+
+    // Calendar date of the day, as year-month-day
+    pub fn date(&self) -> String {
+        // Howard Hinnant's days_from_civil, run backwards
+        let z = self.day as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        format!("{}-{:02}-{:02}", year, month, day)
+    }
+}
+
+// The challenge being played, None outside of challenge mode
+#[derive(Resource, Default, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengeChoice(pub Option<Challenge>);
+
+impl ChallengeChoice {
+    fn has(&self, modifier: Modifier) -> bool {
+        self.0
+            .as_ref()
+            .is_some_and(|challenge| challenge.modifiers.contains(&modifier))
+    }
+
+    pub fn allows(&self, crop: &Crop) -> bool {
+        match crop {
+            Crop::Carrot => true,
+            Crop::Clover => !self.has(Modifier::NoClover),
+            Crop::Wheat => !self.has(Modifier::NoWheat),
+        }
+    }
+
+    pub fn enemy_count(&self, kind: EnemyKind) -> f32 {
+        let doubled = match kind.nest_kind() {
+            EnemyKind::Caterpillar => self.has(Modifier::DoubleCaterpillars),
+            _ => self.has(Modifier::DoubleAphids),
+        };
+
+        if doubled {
+            2.0
+        } else {
+            1.0
+        }
+    }
+
+    pub fn starting_compost(&self, base: u32) -> u32 {
+        if self.has(Modifier::HalfCompost) {
+            base / 2
+        } else {
+            base
+        }
+    }
+}
+
+// Best run of each day, kept next to the saves
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChallengeBests {
+    pub days: HashMap<u64, Score>,
+    // Whether the run that just ended beat the day's best
+    #[serde(skip)]
+    pub latest: bool,
+}
+
+impl ChallengeBests {
+    fn load() -> Self {
+        let path = save_dir().join(CHALLENGE_FILE);
+        let Ok(text) = std::fs::read_to_string(&path) else { return ChallengeBests::default() };
+
+        ron::from_str(&text).unwrap_or_else(|err| {
+            warn!("Couldn't parse challenge bests {:?}: {}", path, err);
+            ChallengeBests::default()
+        })
+    }
+
+    fn write(&self) {
+        let path = save_dir().join(CHALLENGE_FILE);
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                std::fs::create_dir_all(save_dir())
+                    .and_then(|_| std::fs::write(&path, text))
+                    .map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            warn!("Couldn't save challenge bests to {:?}: {}", path, err);
+        }
+    }
+}
+
+// Sets up a game of today's challenge, ready for GameState::InGame
+pub fn start_challenge(cmd: &mut Commands, seed: &mut SeedSetting) {
+    let challenge = Challenge::today();
+
+    seed.0 = Some(challenge.seed);
+    cmd.insert_resource(LevelChoice(challenge.level.clone()));
+    cmd.insert_resource(ScenarioChoice(None));
+    cmd.insert_resource(Difficulty::Normal);
+    cmd.insert_resource(ChallengeChoice(Some(challenge)));
+    cmd.insert_resource(NextState(GameState::InGame));
+}
+
+pub fn spawn_challenge_info(
+    parent: &mut ChildBuilder,
+    assets: &AssetServer,
+    challenge: &Challenge,
+    map: &str,
+    bests: &ChallengeBests,
+) {
+    let best = match bests.days.get(&challenge.day) {
+        Some(score) => format!(
+            "Today's best: {:02}:{:02}, wave {}",
+            score.seconds / 60,
+            score.seconds % 60,
+            score.wave
+        ),
+        None => "No run yet today".to_string(),
+    };
+    let lines = [
+        format!("{}  #{}", challenge.date(), challenge.seed),
+        format!("Map: {}", map),
+        format!(
+            "Modifiers: {}",
+            challenge
+                .modifiers
+                .iter()
+                .map(Modifier::name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        best,
+    ];
+
+    for line in lines {
+        parent.spawn(TextBundle::from_section(
+            line,
+            TextStyle {
+                font: assets.load("fonts/ModeSeven.ttf"),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        ));
+    }
+}
+
+#[derive(Component)]
+pub struct ChallengeText;
+
+pub struct Plugin;
+
+impl Plugin {
+    fn init(mut cmd: Commands, assets: Res<AssetServer>, challenge: Res<ChallengeChoice>) {
+        let Some(challenge) = &challenge.0 else { return };
+
+        cmd.spawn((
+            TextBundle::from_section(
+                format!(
+                    "Daily challenge {}\n{}",
+                    challenge.date(),
+                    challenge
+                        .modifiers
+                        .iter()
+                        .map(Modifier::name)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ),
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 16.0,
+                    color: Color::YELLOW,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+            ChallengeText,
+        ));
+    }
+
+    // The seed was only set for the challenge, it shouldn't carry over into the next game
+    fn leave(mut challenge: ResMut<ChallengeChoice>, mut seed: ResMut<SeedSetting>) {
+        if challenge.0.take().is_some() {
+            seed.0 = None;
+        }
+    }
+
+    fn record(
+        mut bests: ResMut<ChallengeBests>,
+        challenge: Res<ChallengeChoice>,
+        director: Res<WaveDirector>,
+        compost: Res<Compost>,
+        stats: Res<MatchStats>,
+        rng: Res<GameRng>,
+        mode: Option<Res<ReplayMode>>,
+    ) {
+        bests.latest = false;
+
+        if mode.is_some_and(|mode| mode.is_playback()) {
+            return;
+        }
+        let Some(challenge) = &challenge.0 else { return };
+
+        let score = Score {
+            seconds: director.total.as_secs(),
+            wave: director.wave,
+            kills: stats.total_kills(),
+            compost: compost.earned,
+            difficulty: Difficulty::Normal,
+            seed: rng.seed,
+        };
+
+        if bests
+            .days
+            .get(&challenge.day)
+            .is_some_and(|best| best.seconds >= score.seconds)
+        {
+            return;
+        }
+
+        bests.days.insert(challenge.day, score);
+        bests.latest = true;
+        bests.write();
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ChallengeBests::load())
+            .init_resource::<ChallengeChoice>()
+            .add_enter_system(GameState::InGame, Self::init)
+            .add_enter_system(GameState::MainMenu, Self::leave)
+            .add_enter_system(MatchState::GameOver, Self::record.label("record_score"))
+            .add_enter_system(MatchState::Victory, Self::record.label("record_score"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_day_gives_the_same_challenge() {
+        assert_eq!(Challenge::for_day(20088), Challenge::for_day(20088));
+    }
+
+    #[test]
+    fn neighbouring_days_get_different_seeds() {
+        let seeds: Vec<u64> = (20000..20010)
+            .map(|day| Challenge::for_day(day).seed)
+            .collect();
+        for (i, seed) in seeds.iter().enumerate() {
+            assert!(!seeds[i + 1..].contains(seed));
+        }
+    }
+
+    #[test]
+    fn challenges_pick_distinct_modifiers_and_a_bundled_level() {
+        for day in 20000..20050 {
+            let challenge = Challenge::for_day(day);
+
+            assert_eq!(challenge.day, day);
+            assert_eq!(challenge.modifiers.len(), CHALLENGE_MODIFIERS);
+            for (i, modifier) in challenge.modifiers.iter().enumerate() {
+                assert!(!challenge.modifiers[i + 1..].contains(modifier));
+            }
+            assert!(LEVELS.contains(&challenge.level.as_str()));
+        }
+    }
+
+    #[test]
+    fn days_are_shown_as_calendar_dates() {
+        assert_eq!(Challenge::for_day(0).date(), "1970-01-01");
+        assert_eq!(Challenge::for_day(11016).date(), "2000-02-29");
+        assert_eq!(Challenge::for_day(20088).date(), "2024-12-31");
+    }
+}
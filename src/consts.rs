@@ -65,6 +65,10 @@ pub const APHID_ATTACK_RANGE: f32 = 10.0;
 pub const APHID_ATTACK_SPEED: f32 = 0.5;
 pub const APHID_HEALTH: f32 = 5.0;
 pub const APHID_DAMAGE: f32 = 0.51;
+pub const APHID_BREED_TIME: f32 = 8.0;
+pub const APHID_COLONY_RANGE: f32 = 32.0;
+pub const APHID_COLONY_CAP: u32 = 6;
+pub const APHID_OFFSPRING_SPREAD: f32 = 12.0;
 
 pub const CATERPILLAR_MOVE_SPEED: f32 = 25.0;
 pub const CATERPILLAR_AGGRO_RANGE: f32 = 25.0;
@@ -74,11 +78,99 @@ pub const CATERPILLAR_ATTACK_RANGE: f32 = 16.0;
 pub const CATERPILLAR_ATTACK_SPEED: f32 = 2.0;
 pub const CATERPILLAR_HEALTH: f32 = 25.0;
 pub const CATERPILLAR_DAMAGE: f32 = 7.0;
+pub const CATERPILLAR_COCOON_TIME: f32 = 30.0;
+
+pub const COCOON_HATCH_TIME: f32 = 15.0;
+
+pub const BUTTERFLY_MOVE_SPEED: f32 = 120.0;
+pub const BUTTERFLY_AGGRO_RANGE: f32 = 25.0;
+pub const BUTTERFLY_CHASE_RANGE: f32 = 50.0;
+pub const BUTTERFLY_LEASH_RANGE: f32 = 100.0;
+pub const BUTTERFLY_ATTACK_RANGE: f32 = 10.0;
+pub const BUTTERFLY_ATTACK_SPEED: f32 = 1.0;
+pub const BUTTERFLY_HEALTH: f32 = 8.0;
+pub const BUTTERFLY_DAMAGE: f32 = 1.0;
+pub const BUTTERFLY_EGG_COUNT: u32 = 3;
+pub const BUTTERFLY_LAY_TIME: f32 = 2.0;
+
+pub const EGG_HATCH_TIME: f32 = 12.0;
+pub const EGG_HEALTH: f32 = 3.0;
+
+pub const LADYBUG_COUNT: u32 = 2;
+pub const LADYBUG_MOVE_SPEED: f32 = 60.0;
+pub const LADYBUG_AGGRO_RANGE: f32 = 40.0;
+pub const LADYBUG_CHASE_RANGE: f32 = 60.0;
+pub const LADYBUG_LEASH_RANGE: f32 = 120.0;
+pub const LADYBUG_ATTACK_RANGE: f32 = 10.0;
+pub const LADYBUG_ATTACK_SPEED: f32 = 1.0;
+pub const LADYBUG_HEALTH: f32 = 12.0;
+pub const LADYBUG_DAMAGE: f32 = 2.0;
+pub const LADYBUG_WANDER_RADIUS: f32 = 80.0;
+
+pub const BEE_COUNT: u32 = 3;
+pub const BEE_MOVE_SPEED: f32 = 90.0;
+pub const BEE_AGGRO_RANGE: f32 = 40.0;
+pub const BEE_CHASE_RANGE: f32 = 80.0;
+pub const BEE_LEASH_RANGE: f32 = 150.0;
+pub const BEE_ATTACK_RANGE: f32 = 10.0;
+pub const BEE_ATTACK_SPEED: f32 = 0.8;
+pub const BEE_HEALTH: f32 = 6.0;
+pub const BEE_DAMAGE: f32 = 3.0;
+pub const BEE_WANDER_RADIUS: f32 = 30.0;
+
+pub const WANDER_TIME: f32 = 4.0;
+pub const WILDLIFE_SPAWN_TIME: f32 = 45.0;
+pub const WILDLIFE_AREA: Vec2 = Vec2::new(280.0, 150.0);
+pub const PROVOKED_TIME: f32 = 10.0;
+
+pub const ROSE_HEALTH: f32 = 100.0;
+
+pub const WAVE_SCRIPT: &str = "garden.waves.ron";
+
+pub const NEST_HEALTH: f32 = 60.0;
+pub const NEST_REGROW_TIME: f32 = 60.0;
+pub const NEST_SPAWN_SPREAD: f32 = 16.0;
 
 pub const SELECTION_COLLISION_GROUP: Group = Group::GROUP_31;
 pub const UNIT_COLLISION_GROUP: Group = Group::GROUP_1;
 
-pub const FRIENDLY_COLLISION_GROUP: Group = Group::GROUP_30;
-pub const ENEMY_COLLISION_GROUP: Group = Group::GROUP_29;
-
 pub const TARGET_MOVEMENT_SLOP: f32 = 16.0;
+
+pub const SIM_TIMESTEP: f32 = 1.0 / 30.0;
+// Picked with the number keys, 1 through 4
+pub const GAME_SPEEDS: [f32; 4] = [0.5, 1.0, 2.0, 4.0];
+
+// Folder in the platform's data directory that everything below goes in
+pub const DATA_DIR: &str = "harvrts";
+pub const REPLAY_DIR: &str = "replays";
+pub const SAVE_DIR: &str = "saves";
+pub const QUICKSAVE: &str = "quicksave.ron";
+pub const AUTOSAVE_INTERVAL: u64 = 60;
+pub const AUTOSAVE_SLOTS: usize = 3;
+pub const SESSION_LOCK: &str = "session.lock";
+pub const SETTINGS_FILE: &str = "settings.ron";
+pub const SCORES_FILE: &str = "scores.ron";
+pub const MAX_HIGH_SCORES: usize = 10;
+// Seconds between points on the post-game timeline
+pub const STATS_SAMPLE_INTERVAL: f32 = 5.0;
+pub const CHALLENGE_FILE: &str = "challenges.ron";
+// How many modifiers each daily challenge rolls
+pub const CHALLENGE_MODIFIERS: usize = 2;
+pub const ACHIEVEMENTS_FILE: &str = "achievements.ron";
+pub const ACHIEVEMENT_TOAST_TIME: f32 = 4.0;
+pub const LEVEL_DIR: &str = "maps";
+pub const LEVEL_EXTENSION: &str = ".level.ron";
+pub const CAMPAIGN: &str = "garden.campaign.ron";
+pub const CAMPAIGN_FILE: &str = "campaign.ron";
+pub const EDITOR_GRID: f32 = 4.0;
+pub const EDITOR_PICK_RADIUS: f32 = 12.0;
+// In the order the map picker lists them, the first is the default
+pub const LEVELS: [&str; 3] = [
+    "garden.level.ron",
+    "allotment.level.ron",
+    "hedgerow.level.ron",
+];
+// Loaded with the rest but kept out of the map picker
+pub const TUTORIAL_LEVEL: &str = "tutorial.level.ron";
+pub const SPAWN_WARNING_TIME: f32 = 2.0;
+pub const EDGE_ARROW_MARGIN: f32 = 12.0;
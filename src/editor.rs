@@ -0,0 +1,449 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    consts::{EDITOR_GRID, EDITOR_PICK_RADIUS, LEVEL_EXTENSION, PLOT_SIZE},
+    enemy::EnemyKind,
+    level::{Level, LevelChoice, LevelNest, LevelPlot, Levels, Obstacle},
+    scenario::ScenarioChoice,
+    utils::MousePosition,
+    GameState,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Plot,
+    LockedPlot,
+    Rose,
+    Obstacle,
+    AphidNest,
+    CaterpillarNest,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 6] = [
+        Tool::Plot,
+        Tool::LockedPlot,
+        Tool::Rose,
+        Tool::Obstacle,
+        Tool::AphidNest,
+        Tool::CaterpillarNest,
+    ];
+
+    const KEYS: [KeyCode; 6] = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Plot => "Plot",
+            Tool::LockedPlot => "Locked plot",
+            Tool::Rose => "Rose",
+            Tool::Obstacle => "Obstacle (drag)",
+            Tool::AphidNest => "Aphid nest",
+            Tool::CaterpillarNest => "Caterpillar nest",
+        }
+    }
+}
+
+// The map being edited, kept while it's being test-played so the edits survive
+#[derive(Resource)]
+pub struct Editor {
+    pub level: Level,
+    tool: Tool,
+    drag_start: Option<Vec2>,
+    unsaved: bool,
+    // Key that drops the unsaved edits if it's pressed again
+    confirm_discard: Option<KeyCode>,
+}
+
+impl Editor {
+    fn new(level: Level) -> Self {
+        Editor {
+            level,
+            tool: Tool::Plot,
+            drag_start: None,
+            unsaved: false,
+            confirm_discard: None,
+        }
+    }
+
+    fn place(&mut self, pos: Vec2) {
+        let level = &mut self.level;
+        match self.tool {
+            Tool::Plot => level.plots.push(LevelPlot { pos, locked: false }),
+            Tool::LockedPlot => level.plots.push(LevelPlot { pos, locked: true }),
+            Tool::Rose => level.rose = pos,
+            Tool::Obstacle => self.drag_start = Some(pos),
+            Tool::AphidNest => level.nests.push(LevelNest {
+                kind: EnemyKind::Aphid,
+                pos,
+            }),
+            Tool::CaterpillarNest => level.nests.push(LevelNest {
+                kind: EnemyKind::Caterpillar,
+                pos,
+            }),
+        }
+        self.edited();
+    }
+
+    fn edited(&mut self) {
+        self.unsaved = true;
+        self.confirm_discard = None;
+    }
+
+    fn finish_drag(&mut self, pos: Vec2) {
+        let Some(start) = self.drag_start.take() else { return };
+        let size = (pos - start).abs().max(Vec2::splat(EDITOR_GRID));
+
+        self.level.obstacles.push(Obstacle {
+            pos: (start + pos) / 2.0,
+            size,
+        });
+        self.edited();
+    }
+
+    // Takes out whatever is closest to the cursor, the rose can only be moved
+    fn remove(&mut self, pos: Vec2) {
+        let level = &mut self.level;
+        let plot = closest(level.plots.iter().map(|plot| plot.pos), pos);
+        let obstacle = closest(level.obstacles.iter().map(|obstacle| obstacle.pos), pos);
+        let nest = closest(level.nests.iter().map(|nest| nest.pos), pos);
+
+        let distance = |found: Option<(usize, f32)>| found.map_or(f32::MAX, |v| v.1);
+        let nearest = distance(plot).min(distance(obstacle)).min(distance(nest));
+
+        if let Some((i, _)) = plot.filter(|v| v.1 == nearest) {
+            level.plots.remove(i);
+        } else if let Some((i, _)) = obstacle.filter(|v| v.1 == nearest) {
+            level.obstacles.remove(i);
+        } else if let Some((i, _)) = nest.filter(|v| v.1 == nearest) {
+            level.nests.remove(i);
+        } else {
+            return;
+        }
+        self.edited();
+    }
+
+    fn blank(levels: &Levels) -> Level {
+        let file = (1..)
+            .map(|i| format!("custom_{}{}", i, LEVEL_EXTENSION))
+            .find(|file| levels.maps.iter().all(|level| &level.file != file))
+            .unwrap();
+
+        Level {
+            file,
+            name: "Custom Map".to_string(),
+            rose: Vec2::ZERO,
+            starting_compost: 100,
+            plots: Vec::new(),
+            obstacles: Vec::new(),
+            nests: Vec::new(),
+        }
+    }
+}
+
+// Set while a map is being test-played from the editor, to come back to it afterwards
+#[derive(Resource)]
+pub struct EditorTest;
+
+#[derive(Component)]
+pub struct EditorItem;
+
+#[derive(Component)]
+pub struct Root;
+
+#[derive(Component)]
+pub struct EditorText;
+
+// Index and distance of the nearest position within reach
+fn closest(positions: impl Iterator<Item = Vec2>, pos: Vec2) -> Option<(usize, f32)> {
+    positions
+        .enumerate()
+        .map(|(i, other)| (i, other.distance(pos)))
+        .filter(|(_, distance)| *distance < EDITOR_PICK_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+fn snap(pos: Vec3) -> Vec2 {
+    (pos.truncate() / EDITOR_GRID).round() * EDITOR_GRID
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn init(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        levels: Res<Levels>,
+        choice: Res<LevelChoice>,
+        editor: Option<ResMut<Editor>>,
+    ) {
+        match editor {
+            // Redraw whatever was being edited before the test-play
+            Some(mut editor) => editor.set_changed(),
+            None => cmd.insert_resource(Editor::new(levels.get(&choice).clone())),
+        }
+
+        cmd.spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+            Root,
+            EditorText,
+        ));
+    }
+
+    fn cleanup(
+        mut cmd: Commands,
+        q_root: Query<Entity, With<Root>>,
+        q_item: Query<Entity, With<EditorItem>>,
+    ) {
+        for entity in q_root.iter().chain(&q_item) {
+            cmd.entity(entity).despawn_recursive();
+        }
+    }
+
+    fn return_from_test(mut cmd: Commands) {
+        cmd.remove_resource::<EditorTest>();
+        cmd.insert_resource(NextState(GameState::Editor));
+    }
+
+    fn edit(
+        mut editor: ResMut<Editor>,
+        keyboard: Res<Input<KeyCode>>,
+        mouse: Res<Input<MouseButton>>,
+        mouse_pos: Res<MousePosition>,
+    ) {
+        for (tool, key) in Tool::ALL.into_iter().zip(Tool::KEYS) {
+            if keyboard.just_pressed(key) {
+                editor.tool = tool;
+            }
+        }
+
+        let pos = snap(mouse_pos.0);
+
+        if mouse.just_pressed(MouseButton::Left) {
+            editor.place(pos);
+        }
+        if mouse.just_released(MouseButton::Left) {
+            editor.finish_drag(pos);
+        }
+        if mouse.just_pressed(MouseButton::Right) {
+            editor.remove(pos);
+        }
+    }
+
+    fn handle_keys(
+        mut cmd: Commands,
+        mut editor: ResMut<Editor>,
+        mut levels: ResMut<Levels>,
+        keyboard: Res<Input<KeyCode>>,
+    ) {
+        if keyboard.just_pressed(KeyCode::S) {
+            editor.level.write();
+            editor.unsaved = false;
+            editor.confirm_discard = None;
+            levels.replace(editor.level.clone());
+        }
+
+        if keyboard.just_pressed(KeyCode::T) {
+            levels.replace(editor.level.clone());
+            cmd.insert_resource(LevelChoice(editor.level.file.clone()));
+            cmd.insert_resource(ScenarioChoice(None));
+            cmd.insert_resource(EditorTest);
+            cmd.insert_resource(NextState(GameState::InGame));
+        }
+
+        // Anything that drops unsaved edits asks first, pressing the same key again goes ahead
+        let discard = [
+            KeyCode::N,
+            KeyCode::LBracket,
+            KeyCode::RBracket,
+            KeyCode::Escape,
+        ]
+        .into_iter()
+        .find(|key| keyboard.just_pressed(*key));
+        if let Some(key) = discard {
+            if editor.unsaved && editor.confirm_discard != Some(key) {
+                editor.confirm_discard = Some(key);
+                return;
+            }
+        }
+
+        if keyboard.just_pressed(KeyCode::N) {
+            *editor = Editor::new(Editor::blank(&levels));
+        }
+
+        let step = if keyboard.just_pressed(KeyCode::RBracket) {
+            1
+        } else if keyboard.just_pressed(KeyCode::LBracket) {
+            levels.maps.len().saturating_sub(1)
+        } else {
+            0
+        };
+        if step != 0 && !levels.maps.is_empty() {
+            let current = levels
+                .maps
+                .iter()
+                .position(|level| level.file == editor.level.file)
+                .unwrap_or(0);
+            let level = &levels.maps[(current + step) % levels.maps.len()];
+            *editor = Editor::new(level.clone());
+        }
+
+        if keyboard.just_pressed(KeyCode::Escape) {
+            cmd.remove_resource::<Editor>();
+            cmd.insert_resource(NextState(GameState::MainMenu));
+        }
+    }
+
+    fn redraw(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        editor: Res<Editor>,
+        q_item: Query<Entity, With<EditorItem>>,
+    ) {
+        if !editor.is_changed() {
+            return;
+        }
+
+        for entity in &q_item {
+            cmd.entity(entity).despawn_recursive();
+        }
+
+        let level = &editor.level;
+        let mut sprite = |texture: &str, pos: Vec2, z: f32, sprite: Sprite| {
+            cmd.spawn((
+                SpriteBundle {
+                    texture: assets.load(texture),
+                    transform: Transform::from_translation(pos.extend(z)),
+                    sprite,
+                    ..default()
+                },
+                EditorItem,
+            ));
+        };
+
+        for plot in &level.plots {
+            let texture = if plot.locked { "rocks.png" } else { "plot.png" };
+            sprite(
+                texture,
+                plot.pos,
+                0.0,
+                Sprite {
+                    custom_size: Some(PLOT_SIZE),
+                    ..default()
+                },
+            );
+        }
+        for obstacle in &level.obstacles {
+            sprite(
+                "rocks.png",
+                obstacle.pos,
+                0.05,
+                Sprite {
+                    custom_size: Some(obstacle.size),
+                    ..default()
+                },
+            );
+        }
+        for nest in &level.nests {
+            sprite(
+                "nest.png",
+                nest.pos,
+                0.05,
+                Sprite {
+                    color: nest.kind.nest_colour(),
+                    ..default()
+                },
+            );
+        }
+        sprite(
+            "rose.png",
+            level.rose,
+            0.5,
+            Sprite {
+                anchor: bevy::sprite::Anchor::BottomCenter,
+                ..default()
+            },
+        );
+    }
+
+    fn update_text(editor: Res<Editor>, mut q_text: Query<&mut Text, With<EditorText>>) {
+        let mut text = q_text.single_mut();
+
+        let tools = Tool::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, tool)| {
+                let marker = if *tool == editor.tool { ">" } else { " " };
+                format!("{}{} {}", marker, i + 1, tool.name())
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        text.sections[0].value = format!(
+            "{} ({}){}\n{}\nLeft click place, right click remove\nS save, T test, N new, [ ] switch map, Esc menu{}",
+            editor.level.name,
+            editor.level.file,
+            if editor.unsaved { " *" } else { "" },
+            tools,
+            if editor.confirm_discard.is_some() {
+                "\nUnsaved changes, press again to discard them"
+            } else {
+                ""
+            }
+        );
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_enter_system(GameState::Editor, Self::init)
+            .add_exit_system(GameState::Editor, Self::cleanup)
+            .add_enter_system(
+                GameState::MainMenu,
+                Self::return_from_test.run_if_resource_exists::<EditorTest>(),
+            )
+            .add_system(
+                Self::edit
+                    .run_in_state(GameState::Editor)
+                    .label("editor_input"),
+            )
+            .add_system(
+                Self::handle_keys
+                    .run_in_state(GameState::Editor)
+                    .label("editor_input"),
+            )
+            .add_system(
+                Self::redraw
+                    .run_in_state(GameState::Editor)
+                    .after("editor_input"),
+            )
+            .add_system(
+                Self::update_text
+                    .run_in_state(GameState::Editor)
+                    .after("editor_input"),
+            );
+    }
+}
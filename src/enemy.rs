@@ -0,0 +1,691 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::*,
+    faction::Faction,
+    game::Rose,
+    health::{Dead, Health, HealthBar},
+    level::{LevelChoice, Levels},
+    plot::Plot,
+    rng::{GameRng, RngStream},
+    sim::{SimTime, SIM, SIM_AI, SIM_COMMANDS, SIM_DEATH},
+    unit::{Enemy, Unit, UnitCommand},
+    utils::Bar,
+    waves::WaveModifiers,
+    GameState,
+};
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EnemyKind {
+    Aphid,
+    Caterpillar,
+    Butterfly,
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Breeder {
+    timer: Timer,
+    #[serde(skip)]
+    colony: Option<Entity>,
+}
+
+impl Breeder {
+    pub fn new() -> Self {
+        Breeder {
+            timer: Timer::from_seconds(APHID_BREED_TIME, TimerMode::Repeating),
+            colony: None,
+        }
+    }
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Larva {
+    timer: Timer,
+}
+
+impl Larva {
+    pub fn new() -> Self {
+        Larva {
+            timer: Timer::from_seconds(CATERPILLAR_COCOON_TIME, TimerMode::Once),
+        }
+    }
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Cocoon {
+    timer: Timer,
+}
+
+impl Cocoon {
+    pub fn new() -> Self {
+        Cocoon {
+            timer: Timer::from_seconds(COCOON_HATCH_TIME, TimerMode::Once),
+        }
+    }
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct EggLayer {
+    eggs: u32,
+    timer: Timer,
+    #[serde(skip)]
+    target: Option<Entity>,
+}
+
+impl EggLayer {
+    pub fn new() -> Self {
+        EggLayer {
+            eggs: BUTTERFLY_EGG_COUNT,
+            timer: Timer::from_seconds(BUTTERFLY_LAY_TIME, TimerMode::Once),
+            target: None,
+        }
+    }
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Egg {
+    timer: Timer,
+}
+
+impl Egg {
+    pub fn new() -> Self {
+        Egg {
+            timer: Timer::from_seconds(EGG_HATCH_TIME, TimerMode::Once),
+        }
+    }
+}
+
+pub fn spawn_enemy(cmd: &mut Commands, assets: &AssetServer, kind: EnemyKind, pos: Vec2) -> Entity {
+    let common = (
+        RigidBody::Dynamic,
+        Velocity::default(),
+        LockedAxes::ROTATION_LOCKED_Z,
+        CollisionGroups {
+            memberships: UNIT_COLLISION_GROUP | Faction::Pests.group(),
+            filters: UNIT_COLLISION_GROUP,
+        },
+        Damping {
+            linear_damping: 20.0,
+            angular_damping: 0.0,
+        },
+        Enemy,
+        kind,
+        Faction::Pests,
+        WaveModifiers::default(),
+    );
+
+    match kind {
+        EnemyKind::Aphid => cmd
+            .spawn((
+                SpriteBundle {
+                    texture: assets.load("aphid.png"),
+                    transform: Transform::from_translation(pos.extend(0.1)),
+                    ..default()
+                },
+                Collider::ball(4.0),
+                Unit::new(
+                    APHID_MOVE_SPEED,
+                    APHID_AGGRO_RANGE,
+                    APHID_CHASE_RANGE,
+                    APHID_ATTACK_RANGE,
+                    APHID_LEASH_RANGE,
+                    APHID_ATTACK_SPEED,
+                    APHID_DAMAGE,
+                ),
+                Health::new(APHID_HEALTH),
+                Breeder::new(),
+                common,
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    SpriteBundle {
+                        transform: Transform::from_translation(Vec3::new(0.0, -4.0, 0.1)),
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::new(2.0, 1.0)),
+                            color: Color::RED,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Bar {
+                        value: APHID_HEALTH,
+                        max: APHID_HEALTH,
+                        size: 10.0,
+                    },
+                    HealthBar,
+                ));
+            })
+            .id(),
+        EnemyKind::Caterpillar => cmd
+            .spawn((
+                SpriteBundle {
+                    texture: assets.load("caterpillar.png"),
+                    transform: Transform::from_translation(pos.extend(0.1)),
+                    ..default()
+                },
+                Collider::ball(8.0),
+                Unit::new(
+                    CATERPILLAR_MOVE_SPEED,
+                    CATERPILLAR_AGGRO_RANGE,
+                    CATERPILLAR_CHASE_RANGE,
+                    CATERPILLAR_ATTACK_RANGE,
+                    CATERPILLAR_LEASH_RANGE,
+                    CATERPILLAR_ATTACK_SPEED,
+                    CATERPILLAR_DAMAGE,
+                ),
+                Health::new(CATERPILLAR_HEALTH),
+                Larva::new(),
+                common,
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    SpriteBundle {
+                        transform: Transform::from_translation(Vec3::new(0.0, -4.0, 0.1)),
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::new(2.0, 1.0)),
+                            color: Color::RED,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Bar {
+                        value: CATERPILLAR_HEALTH,
+                        max: CATERPILLAR_HEALTH,
+                        size: 20.0,
+                    },
+                    HealthBar,
+                ));
+            })
+            .id(),
+        EnemyKind::Butterfly => cmd
+            .spawn((
+                SpriteBundle {
+                    texture: assets.load("butterfly.png"),
+                    transform: Transform::from_translation(pos.extend(0.2)),
+                    ..default()
+                },
+                Collider::ball(4.0),
+                // Butterflies fly over everything instead of pushing through it
+                SolverGroups {
+                    memberships: Group::ALL,
+                    filters: Group::NONE,
+                },
+                Unit::new(
+                    BUTTERFLY_MOVE_SPEED,
+                    BUTTERFLY_AGGRO_RANGE,
+                    BUTTERFLY_CHASE_RANGE,
+                    BUTTERFLY_ATTACK_RANGE,
+                    BUTTERFLY_LEASH_RANGE,
+                    BUTTERFLY_ATTACK_SPEED,
+                    BUTTERFLY_DAMAGE,
+                ),
+                Health::new(BUTTERFLY_HEALTH),
+                EggLayer::new(),
+                common,
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    SpriteBundle {
+                        transform: Transform::from_translation(Vec3::new(0.0, -6.0, 0.1)),
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::new(2.0, 1.0)),
+                            color: Color::RED,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Bar {
+                        value: BUTTERFLY_HEALTH,
+                        max: BUTTERFLY_HEALTH,
+                        size: 10.0,
+                    },
+                    HealthBar,
+                ));
+            })
+            .id(),
+    }
+}
+
+#[derive(Component)]
+pub struct Nest {
+    pub kind: EnemyKind,
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct NestRegrowth {
+    kind: EnemyKind,
+    timer: Timer,
+}
+
+impl NestRegrowth {
+    pub fn new(kind: EnemyKind) -> Self {
+        NestRegrowth {
+            kind,
+            timer: Timer::from_seconds(NEST_REGROW_TIME, TimerMode::Once),
+        }
+    }
+}
+
+pub fn spawn_nest(cmd: &mut Commands, assets: &AssetServer, kind: EnemyKind, pos: Vec2) -> Entity {
+    cmd.spawn((
+        SpriteBundle {
+            texture: assets.load("nest.png"),
+            transform: Transform::from_translation(pos.extend(0.05)),
+            sprite: Sprite {
+                color: kind.nest_colour(),
+                ..default()
+            },
+            ..default()
+        },
+        RigidBody::Fixed,
+        Collider::ball(7.0),
+        CollisionGroups {
+            memberships: UNIT_COLLISION_GROUP | Faction::Pests.group(),
+            filters: UNIT_COLLISION_GROUP,
+        },
+        Health::new(NEST_HEALTH),
+        Faction::Pests,
+        Nest { kind },
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            SpriteBundle {
+                transform: Transform::from_translation(Vec3::new(0.0, -9.0, 0.1)),
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(2.0, 1.0)),
+                    color: Color::RED,
+                    ..default()
+                },
+                ..default()
+            },
+            Bar {
+                value: NEST_HEALTH,
+                max: NEST_HEALTH,
+                size: 20.0,
+            },
+            HealthBar,
+        ));
+    })
+    .id()
+}
+
+// The faded outline a destroyed nest leaves behind until it grows back
+pub fn spawn_nest_regrowth(
+    cmd: &mut Commands,
+    assets: &AssetServer,
+    regrowth: NestRegrowth,
+    pos: Vec2,
+) -> Entity {
+    let mut colour = regrowth.kind.nest_colour();
+    colour.set_a(0.3);
+
+    cmd.spawn((
+        SpriteBundle {
+            texture: assets.load("nest.png"),
+            transform: Transform::from_translation(pos.extend(0.05)),
+            sprite: Sprite {
+                color: colour,
+                ..default()
+            },
+            ..default()
+        },
+        regrowth,
+    ))
+    .id()
+}
+
+pub fn spawn_cocoon(cmd: &mut Commands, assets: &AssetServer, pos: Vec2) -> Entity {
+    let entity = spawn_enemy(cmd, assets, EnemyKind::Caterpillar, pos);
+    cmd.entity(entity).remove::<(Larva, Unit)>().insert((
+        assets.load::<Image, _>("cocoon.png"),
+        Cocoon::new(),
+        RigidBody::Fixed,
+    ));
+    entity
+}
+
+pub fn spawn_egg(cmd: &mut Commands, assets: &AssetServer, pos: Vec2) -> Entity {
+    cmd.spawn((
+        SpriteBundle {
+            texture: assets.load("egg.png"),
+            transform: Transform::from_translation(pos.extend(0.05)),
+            ..default()
+        },
+        RigidBody::Fixed,
+        Collider::ball(3.0),
+        CollisionGroups {
+            memberships: UNIT_COLLISION_GROUP | Faction::Pests.group(),
+            filters: UNIT_COLLISION_GROUP,
+        },
+        Health::new(EGG_HEALTH),
+        Faction::Pests,
+        Egg::new(),
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            SpriteBundle {
+                transform: Transform::from_translation(Vec3::new(0.0, -4.0, 0.1)),
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(2.0, 1.0)),
+                    color: Color::RED,
+                    ..default()
+                },
+                ..default()
+            },
+            Bar {
+                value: EGG_HEALTH,
+                max: EGG_HEALTH,
+                size: 6.0,
+            },
+            HealthBar,
+        ));
+    })
+    .id()
+}
+
+impl EnemyKind {
+    pub const ALL: [EnemyKind; 3] = [
+        EnemyKind::Aphid,
+        EnemyKind::Caterpillar,
+        EnemyKind::Butterfly,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EnemyKind::Aphid => "Aphids",
+            EnemyKind::Caterpillar => "Caterpillars",
+            EnemyKind::Butterfly => "Butterflies",
+        }
+    }
+
+    pub fn nest_colour(&self) -> Color {
+        match self {
+            EnemyKind::Aphid => Color::rgb(0.8, 1.0, 0.7),
+            EnemyKind::Caterpillar => Color::rgb(1.0, 0.9, 0.6),
+            EnemyKind::Butterfly => Color::WHITE,
+        }
+    }
+
+    // Butterflies have no nest of their own, they come out of the caterpillar ones
+    pub fn nest_kind(&self) -> EnemyKind {
+        match self {
+            EnemyKind::Butterfly => EnemyKind::Caterpillar,
+            kind => *kind,
+        }
+    }
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn init(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        levels: Res<Levels>,
+        choice: Res<LevelChoice>,
+    ) {
+        for nest in &levels.get(&choice).nests {
+            spawn_nest(&mut cmd, &assets, nest.kind, nest.pos);
+        }
+    }
+
+    fn aphid_breeding(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        time: Res<SimTime>,
+        rng: Res<GameRng>,
+        mut q_breeder: Query<(&mut Breeder, &GlobalTransform), Without<Dead>>,
+        q_food: Query<(Entity, &GlobalTransform, Option<&Plot>), Or<(With<Rose>, With<Plot>)>>,
+    ) {
+        let rng = rng.stream(RngStream::Breeding);
+
+        // Aphids settled on a food source form a colony around it
+        let mut colony_sizes = HashMap::<Entity, u32>::new();
+        for (breeder, _) in &q_breeder {
+            if let Some(colony) = breeder.colony {
+                *colony_sizes.entry(colony).or_default() += 1;
+            }
+        }
+
+        for (mut breeder, transform) in &mut q_breeder {
+            let pos = transform.translation().truncate();
+
+            let mut nearest = None;
+            for (food, food_transform, plot) in &q_food {
+                if matches!(plot, Some(Plot::Locked | Plot::Empty)) {
+                    continue;
+                }
+                let dist = food_transform.translation().truncate().distance(pos);
+                if dist > APHID_COLONY_RANGE {
+                    continue;
+                }
+                match nearest {
+                    Some((_, old_dist)) if old_dist <= dist => {}
+                    _ => nearest = Some((food, dist)),
+                }
+            }
+            let nearest = nearest.map(|(food, _)| food);
+
+            if nearest != breeder.colony {
+                breeder.colony = nearest;
+                breeder.timer.reset();
+                continue;
+            }
+
+            let Some(colony) = breeder.colony else { continue };
+
+            breeder.timer.tick(time.delta());
+
+            if breeder.timer.just_finished() {
+                let size = colony_sizes.entry(colony).or_default();
+                if *size < APHID_COLONY_CAP {
+                    *size += 1;
+                    let offset =
+                        Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5) * APHID_OFFSPRING_SPREAD;
+                    spawn_enemy(&mut cmd, &assets, EnemyKind::Aphid, pos + offset);
+                }
+            }
+        }
+    }
+
+    fn caterpillar_cocooning(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        time: Res<SimTime>,
+        mut q_larva: Query<(Entity, &mut Larva, &mut Handle<Image>, &mut Velocity), Without<Dead>>,
+    ) {
+        for (entity, mut larva, mut texture, mut velocity) in &mut q_larva {
+            larva.timer.tick(time.delta());
+
+            if larva.timer.just_finished() {
+                *texture = assets.load("cocoon.png");
+                velocity.linvel = Vec2::ZERO;
+                cmd.entity(entity)
+                    .remove::<(Larva, Unit)>()
+                    .insert((Cocoon::new(), RigidBody::Fixed));
+            }
+        }
+    }
+
+    fn cocoon_hatching(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        time: Res<SimTime>,
+        mut q_cocoon: Query<(Entity, &mut Cocoon, &GlobalTransform), Without<Dead>>,
+    ) {
+        for (entity, mut cocoon, transform) in &mut q_cocoon {
+            cocoon.timer.tick(time.delta());
+
+            if cocoon.timer.just_finished() {
+                cmd.entity(entity).despawn_recursive();
+                spawn_enemy(
+                    &mut cmd,
+                    &assets,
+                    EnemyKind::Butterfly,
+                    transform.translation().truncate(),
+                );
+            }
+        }
+    }
+
+    fn butterfly_egg_laying(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        time: Res<SimTime>,
+        rng: Res<GameRng>,
+        mut q_butterfly: Query<(&mut EggLayer, &mut Unit, &GlobalTransform), Without<Dead>>,
+        q_plot: Query<(Entity, &Plot, &GlobalTransform)>,
+        q_rose: Query<&Transform, With<Rose>>,
+    ) {
+        let rng = rng.stream(RngStream::EggLaying);
+
+        for (mut layer, mut unit, transform) in &mut q_butterfly {
+            if layer.eggs == 0 {
+                continue;
+            }
+
+            let target = layer
+                .target
+                .and_then(|target| q_plot.get(target).ok())
+                .filter(|(_, plot, _)| !matches!(plot, Plot::Locked));
+
+            let Some((_, _, target)) = target else {
+                let plots = q_plot
+                    .iter()
+                    .filter(|(_, plot, _)| !matches!(plot, Plot::Locked))
+                    .map(|(entity, _, _)| entity)
+                    .collect::<Vec<_>>();
+
+                layer.target = (!plots.is_empty()).then(|| plots[rng.usize(..plots.len())]);
+                layer.timer.reset();
+                continue;
+            };
+
+            let pos = transform.translation().truncate();
+            let target_pos = target.translation().truncate();
+
+            if pos.distance(target_pos) > TARGET_MOVEMENT_SLOP {
+                layer.timer.reset();
+                unit.set_command(UnitCommand::Move(target_pos));
+                continue;
+            }
+
+            layer.timer.tick(time.delta());
+
+            if layer.timer.just_finished() {
+                let offset = Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5) * PLOT_SIZE;
+                spawn_egg(&mut cmd, &assets, target_pos + offset);
+
+                layer.eggs -= 1;
+                layer.target = None;
+
+                if layer.eggs == 0 {
+                    if let Ok(rose) = q_rose.get_single() {
+                        unit.set_command(UnitCommand::AttackMove(rose.translation.truncate()));
+                    }
+                }
+            }
+        }
+    }
+
+    fn egg_hatching(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        time: Res<SimTime>,
+        mut q_egg: Query<(Entity, &mut Egg, &GlobalTransform), Without<Dead>>,
+    ) {
+        for (entity, mut egg, transform) in &mut q_egg {
+            egg.timer.tick(time.delta());
+
+            if egg.timer.just_finished() {
+                cmd.entity(entity).despawn_recursive();
+                spawn_enemy(
+                    &mut cmd,
+                    &assets,
+                    EnemyKind::Caterpillar,
+                    transform.translation().truncate(),
+                );
+            }
+        }
+    }
+
+    fn nest_destroyed(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        q_nest: Query<(&Nest, &GlobalTransform), Added<Dead>>,
+    ) {
+        for (nest, transform) in &q_nest {
+            spawn_nest_regrowth(
+                &mut cmd,
+                &assets,
+                NestRegrowth::new(nest.kind),
+                transform.translation().truncate(),
+            );
+        }
+    }
+
+    fn nest_regrowth(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        time: Res<SimTime>,
+        mut q_regrowth: Query<(Entity, &mut NestRegrowth, &GlobalTransform)>,
+    ) {
+        for (entity, mut regrowth, transform) in &mut q_regrowth {
+            regrowth.timer.tick(time.delta());
+
+            if regrowth.timer.just_finished() {
+                cmd.entity(entity).despawn_recursive();
+                spawn_nest(
+                    &mut cmd,
+                    &assets,
+                    regrowth.kind,
+                    transform.translation().truncate(),
+                );
+            }
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_enter_system(GameState::InGame, Self::init)
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_COMMANDS,
+                Self::aphid_breeding.run_in_state(GameState::InGame),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_COMMANDS,
+                Self::caterpillar_cocooning.run_in_state(GameState::InGame),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_COMMANDS,
+                Self::cocoon_hatching.run_in_state(GameState::InGame),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_AI,
+                Self::butterfly_egg_laying.run_in_state(GameState::InGame),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_COMMANDS,
+                Self::egg_hatching.run_in_state(GameState::InGame),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_DEATH,
+                Self::nest_destroyed.run_in_state(GameState::InGame),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_COMMANDS,
+                Self::nest_regrowth.run_in_state(GameState::InGame),
+            );
+    }
+}
@@ -0,0 +1,148 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    consts::PROVOKED_TIME,
+    health::HealthChange,
+    sim::{SimTime, SIM, SIM_AI, SIM_HEALTH},
+    GameState,
+};
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Faction {
+    Garden,
+    Pests,
+    Predators,
+    Wildlife,
+}
+
+impl Faction {
+    pub const ALL: [Faction; 4] = [
+        Faction::Garden,
+        Faction::Pests,
+        Faction::Predators,
+        Faction::Wildlife,
+    ];
+
+    pub fn group(&self) -> Group {
+        match self {
+            Faction::Garden => Group::GROUP_30,
+            Faction::Pests => Group::GROUP_29,
+            Faction::Predators => Group::GROUP_28,
+            Faction::Wildlife => Group::GROUP_27,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    Hostile,
+    Neutral,
+    Allied,
+}
+
+#[derive(Resource)]
+pub struct Relations(HashMap<(Faction, Faction), Relation>);
+
+impl Default for Relations {
+    fn default() -> Self {
+        let mut relations = Relations(HashMap::default());
+        relations.set(Faction::Garden, Faction::Pests, Relation::Hostile);
+        relations.set(Faction::Predators, Faction::Pests, Relation::Hostile);
+        relations.set(Faction::Garden, Faction::Predators, Relation::Allied);
+        relations
+    }
+}
+
+impl Relations {
+    pub fn set(&mut self, a: Faction, b: Faction, relation: Relation) {
+        self.0.insert((a, b), relation);
+        self.0.insert((b, a), relation);
+    }
+
+    pub fn get(&self, a: Faction, b: Faction) -> Relation {
+        if a == b {
+            return Relation::Allied;
+        }
+        self.0.get(&(a, b)).copied().unwrap_or(Relation::Neutral)
+    }
+
+    pub fn hostile_groups(&self, faction: Faction) -> Group {
+        Faction::ALL
+            .into_iter()
+            .filter(|other| self.get(faction, *other) == Relation::Hostile)
+            .fold(Group::NONE, |groups, other| groups | other.group())
+    }
+}
+
+// Neutral units that got hit turn on the attacker's faction for a while
+#[derive(Component)]
+pub struct Provoked {
+    pub groups: Group,
+    timer: Timer,
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn provoke(
+        mut cmd: Commands,
+        relations: Res<Relations>,
+        mut events: EventReader<HealthChange>,
+        mut q_faction: Query<(&Faction, Option<&mut Provoked>)>,
+    ) {
+        for event in events.iter() {
+            if event.amount >= 0.0 {
+                continue;
+            }
+            let Some(source) = event.source else { continue };
+            let Ok((&attacker, _)) = q_faction.get(source) else { continue };
+            let Ok((&faction, provoked)) = q_faction.get_mut(event.target) else { continue };
+
+            if relations.get(faction, attacker) != Relation::Neutral {
+                continue;
+            }
+
+            if let Some(mut provoked) = provoked {
+                provoked.groups |= attacker.group();
+                provoked.timer.reset();
+            } else {
+                cmd.entity(event.target).insert(Provoked {
+                    groups: attacker.group(),
+                    timer: Timer::from_seconds(PROVOKED_TIME, TimerMode::Once),
+                });
+            }
+        }
+    }
+
+    fn calm_down(
+        mut cmd: Commands,
+        time: Res<SimTime>,
+        mut q_provoked: Query<(Entity, &mut Provoked)>,
+    ) {
+        for (entity, mut provoked) in &mut q_provoked {
+            provoked.timer.tick(time.delta());
+
+            if provoked.timer.just_finished() {
+                cmd.entity(entity).remove::<Provoked>();
+            }
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Relations>()
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_HEALTH,
+                Self::provoke.run_in_state(GameState::InGame),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_AI,
+                Self::calm_down.run_in_state(GameState::InGame),
+            );
+    }
+}
@@ -1,6 +1,10 @@
-use bevy::prelude::*;
+use bevy::{ecs::event::Events, prelude::*};
+use iyes_loopless::prelude::*;
 
-use crate::utils::Bar;
+use crate::{
+    sim::{SIM, SIM_HEALTH, SIM_LAST},
+    utils::Bar,
+};
 
 #[derive(Component)]
 pub struct Health {
@@ -15,6 +19,23 @@ impl Health {
             max: amount,
         }
     }
+
+    pub fn scale(&mut self, factor: f32) {
+        self.current *= factor;
+        self.max *= factor;
+    }
+
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.current / self.max
+    }
+
+    pub fn set_fraction(&mut self, fraction: f32) {
+        self.current = self.max * fraction;
+    }
 }
 
 #[derive(Component)]
@@ -34,6 +55,7 @@ impl DamageFlash {
 pub struct HealthChange {
     pub target: Entity,
     pub amount: f32,
+    pub source: Option<Entity>,
 }
 
 #[derive(Component)]
@@ -90,16 +112,20 @@ impl Plugin {
         }
     }
 
+    // The flash stays on the entity once finished, removing it would shuffle the order the
+    // simulation iterates entities in and throw replays off
     fn update_damage_flash(
-        mut cmd: Commands,
         mut q_flash: Query<(
-            Entity,
             &mut DamageFlash,
             AnyOf<(&mut Sprite, &mut TextureAtlasSprite)>,
         )>,
         time: Res<Time>,
     ) {
-        for (entity, mut flash, sprite) in &mut q_flash {
+        for (mut flash, sprite) in &mut q_flash {
+            if flash.amount >= 1.0 {
+                continue;
+            }
+
             flash.amount += time.delta_seconds() * 5.0;
             flash.amount = flash.amount.min(1.0);
             if let Some(mut sprite) = sprite.0 {
@@ -107,18 +133,16 @@ impl Plugin {
             } else if let Some(mut sprite) = sprite.1 {
                 sprite.color = Color::rgb(10.0 - 9.0 * flash.amount, flash.amount, flash.amount);
             }
-
-            if flash.amount >= 1.0 {
-                cmd.entity(entity).remove::<DamageFlash>();
-            }
         }
     }
 }
 
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<HealthChange>()
-            .add_system(Self::update_health)
+        // Damage is dealt and applied inside simulation ticks, so the events are cleared per tick
+        app.init_resource::<Events<HealthChange>>()
+            .add_fixed_timestep_system(SIM, SIM_HEALTH, Self::update_health)
+            .add_fixed_timestep_system(SIM, SIM_LAST, Events::<HealthChange>::update_system)
             .add_system(Self::init_damage_flash)
             .add_system(Self::update_healthbar)
             .add_system(Self::update_damage_flash);
@@ -0,0 +1,297 @@
+use std::path::Path;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{LEVELS, LEVEL_DIR, LEVEL_EXTENSION, TUTORIAL_LEVEL, UNIT_COLLISION_GROUP},
+    enemy::EnemyKind,
+    GameState,
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LevelPlot {
+    pub pos: Vec2,
+    #[serde(default)]
+    pub locked: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Obstacle {
+    pub pos: Vec2,
+    pub size: Vec2,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LevelNest {
+    pub kind: EnemyKind,
+    pub pos: Vec2,
+}
+
+// Layout of a map, read from `assets/maps`
+#[derive(Clone, Serialize, Deserialize, TypeUuid)]
+#[uuid = "1f96d509-2243-4161-bc20-5dcd33e1610d"]
+pub struct Level {
+    #[serde(skip)]
+    pub file: String,
+    pub name: String,
+    pub rose: Vec2,
+    // On Normal, the difficulty scales it from there
+    pub starting_compost: u32,
+    pub plots: Vec<LevelPlot>,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    // Where the waves come from
+    pub nests: Vec<LevelNest>,
+}
+
+impl Level {
+    // Stand-in for when no map files could be loaded at all
+    fn built_in() -> Self {
+        Level {
+            file: LEVELS[0].to_string(),
+            name: "Rose Garden".to_string(),
+            rose: Vec2::ZERO,
+            starting_compost: 100,
+            plots: [(32.0, 0.0), (0.0, 16.0), (-32.0, 0.0), (0.0, -16.0)]
+                .into_iter()
+                .enumerate()
+                .map(|(i, (x, y))| LevelPlot {
+                    pos: Vec2::new(x, y),
+                    locked: i < 3,
+                })
+                .collect(),
+            obstacles: Vec::new(),
+            nests: vec![
+                LevelNest {
+                    kind: EnemyKind::Aphid,
+                    pos: Vec2::new(-130.0, 55.0),
+                },
+                LevelNest {
+                    kind: EnemyKind::Aphid,
+                    pos: Vec2::new(130.0, -55.0),
+                },
+                LevelNest {
+                    kind: EnemyKind::Caterpillar,
+                    pos: Vec2::new(120.0, 65.0),
+                },
+            ],
+        }
+    }
+
+    // There's no asset folder to write into on the web
+    #[cfg(target_arch = "wasm32")]
+    pub fn write(&self) {
+        warn!("Can't save level {} on the web", self.file);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write(&self) {
+        let path = bevy::asset::FileAssetIo::get_base_path()
+            .join("assets")
+            .join(LEVEL_DIR)
+            .join(&self.file);
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| std::fs::write(&path, text).map_err(|err| err.to_string()));
+
+        match result {
+            Ok(_) => info!("Saved level to {:?}", path),
+            Err(err) => warn!("Couldn't save level to {:?}: {}", path, err),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let level = ron::de::from_bytes::<Level>(bytes)?;
+            let file = load_context
+                .path()
+                .file_name()
+                .and_then(|v| v.to_str())
+                .unwrap_or_default()
+                .to_string();
+            load_context.set_default_asset(LoadedAsset::new(Level { file, ..level }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+// Map files still loading, Levels is made from them once they're all done
+#[derive(Resource)]
+struct LevelHandles(Vec<Handle<Level>>);
+
+// Every map, loaded up front so a game can start on any of them straight away. The bundled
+// ones come first, then anything else made in the editor, then the tutorial
+#[derive(Resource)]
+pub struct Levels {
+    pub maps: Vec<Level>,
+    fallback: Level,
+}
+
+impl Levels {
+    // Swaps in a newer version of a map, or adds it if it's a new one
+    pub fn replace(&mut self, level: Level) {
+        match self.maps.iter_mut().find(|other| other.file == level.file) {
+            Some(other) => *other = level,
+            None => self.maps.push(level),
+        }
+    }
+
+    // Falls back to the first map when the chosen one is missing, e.g. from an old save
+    pub fn get(&self, choice: &LevelChoice) -> &Level {
+        self.maps
+            .iter()
+            .find(|level| level.file == choice.0)
+            .or_else(|| self.maps.first())
+            .unwrap_or(&self.fallback)
+    }
+}
+
+// File name of the map being played
+#[derive(Resource, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelChoice(pub String);
+
+impl Default for LevelChoice {
+    fn default() -> Self {
+        LevelChoice(LEVELS[0].to_string())
+    }
+}
+
+// Maps made in the editor, which can be anything in the folder
+#[cfg(not(target_arch = "wasm32"))]
+fn custom_levels(assets: &AssetServer) -> Vec<Handle<Level>> {
+    let Ok(folder) = assets.load_folder(LEVEL_DIR) else { return Vec::new() };
+
+    folder
+        .into_iter()
+        .filter(|handle| {
+            assets
+                .get_handle_path(handle)
+                .and_then(|path| path.path().to_str().map(str::to_string))
+                .is_some_and(|path| path.ends_with(LEVEL_EXTENSION))
+        })
+        .map(|handle| handle.typed())
+        .collect()
+}
+
+// The web build can't list folders, so it only has the bundled maps
+#[cfg(target_arch = "wasm32")]
+fn custom_levels(_assets: &AssetServer) -> Vec<Handle<Level>> {
+    Vec::new()
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn load(mut cmd: Commands, assets: Res<AssetServer>) {
+        let handles = LEVELS
+            .iter()
+            .chain([&TUTORIAL_LEVEL])
+            .map(|file| assets.load(Path::new(LEVEL_DIR).join(file)))
+            .chain(custom_levels(&assets))
+            .collect();
+
+        cmd.insert_resource(LevelHandles(handles));
+    }
+
+    fn collect(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        handles: Res<LevelHandles>,
+        loaded: Res<Assets<Level>>,
+    ) {
+        if handles
+            .0
+            .iter()
+            .any(|handle| assets.get_load_state(handle) == LoadState::Loading)
+        {
+            return;
+        }
+
+        let mut maps = handles
+            .0
+            .iter()
+            .filter_map(|handle| loaded.get(handle))
+            .cloned()
+            .collect::<Vec<_>>();
+        maps.sort_by_key(|level| {
+            let order = match LEVELS.iter().position(|file| file == &level.file) {
+                Some(i) => i,
+                None if level.file == TUTORIAL_LEVEL => usize::MAX,
+                None => LEVELS.len(),
+            };
+            (order, level.file.clone())
+        });
+        maps.dedup_by(|a, b| a.file == b.file);
+
+        if maps.is_empty() {
+            warn!("Couldn't load any maps, using the built-in one");
+        }
+
+        cmd.remove_resource::<LevelHandles>();
+        cmd.insert_resource(Levels {
+            maps,
+            fallback: Level::built_in(),
+        });
+    }
+
+    fn init(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        levels: Res<Levels>,
+        choice: Res<LevelChoice>,
+    ) {
+        let level = levels.get(&choice);
+
+        for obstacle in &level.obstacles {
+            cmd.spawn((
+                SpriteBundle {
+                    texture: assets.load("rocks.png"),
+                    sprite: Sprite {
+                        custom_size: Some(obstacle.size),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(obstacle.pos.extend(0.05)),
+                    ..default()
+                },
+                RigidBody::Fixed,
+                Collider::cuboid(obstacle.size.x / 2.0, obstacle.size.y / 2.0),
+                CollisionGroups {
+                    memberships: UNIT_COLLISION_GROUP,
+                    filters: UNIT_COLLISION_GROUP,
+                },
+            ));
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .init_resource::<LevelChoice>()
+            .add_startup_system(Self::load)
+            .add_system(Self::collect.run_if_resource_exists::<LevelHandles>())
+            .add_enter_system(GameState::InGame, Self::init);
+    }
+}
@@ -4,20 +4,46 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use iyes_loopless::prelude::*;
 
+mod achievements;
+mod challenge;
 mod consts;
+mod difficulty;
+mod editor;
+mod enemy;
+mod faction;
 mod game;
 mod game_menu;
 mod health;
+mod level;
 mod main_menu;
+mod match_settings;
+mod outcome;
+mod pause;
 mod plot;
+mod replay;
+mod rng;
+mod save;
+mod scenario;
+mod scores;
 mod selection;
+mod settings;
+mod sim;
+mod speed;
+mod stats;
+mod tutorial;
 mod unit;
 mod utils;
+mod warning;
+mod waves;
+mod wildlife;
 
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
 pub enum GameState {
+    // Until the maps and everything else a game reads up front are ready
+    Loading,
     MainMenu,
     InGame,
+    Editor,
 }
 
 #[derive(Component)]
@@ -42,17 +68,42 @@ fn main() {
     )
     .insert_resource(RapierConfiguration {
         gravity: Vec2::ZERO,
+        timestep_mode: TimestepMode::Fixed {
+            dt: consts::SIM_TIMESTEP,
+            substeps: 1,
+        },
         ..default()
     })
-    .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-    .add_loopless_state(GameState::MainMenu)
+    .add_plugin(RapierPhysicsPlugin::<NoUserData>::default().with_default_system_setup(false))
+    .add_plugin(sim::Plugin)
+    .add_loopless_state(GameState::Loading)
     .add_plugin(utils::Plugin)
+    .add_plugin(level::Plugin)
+    .add_plugin(editor::Plugin)
+    .add_plugin(settings::Plugin)
+    .add_plugin(rng::Plugin)
     .add_plugin(main_menu::Plugin)
+    .add_plugin(pause::Plugin)
+    .add_plugin(outcome::Plugin)
+    .add_plugin(speed::Plugin)
     .add_plugin(plot::Plugin)
     .add_plugin(unit::Plugin)
     .add_plugin(game_menu::Plugin)
     .add_plugin(health::Plugin)
     .add_plugin(game::Plugin)
+    .add_plugin(enemy::Plugin)
+    .add_plugin(faction::Plugin)
+    .add_plugin(wildlife::Plugin)
+    .add_plugin(waves::Plugin)
+    .add_plugin(warning::Plugin)
+    .add_plugin(replay::Plugin)
+    .add_plugin(save::Plugin)
+    .add_plugin(scores::Plugin)
+    .add_plugin(scenario::Plugin)
+    .add_plugin(challenge::Plugin)
+    .add_plugin(stats::Plugin)
+    .add_plugin(achievements::Plugin)
+    .add_plugin(tutorial::Plugin)
     .add_plugin(selection::Plugin)
     .add_startup_system(init);
 
@@ -1,34 +1,171 @@
 use bevy::prelude::*;
 
-use crate::GameState;
+use crate::{
+    achievements::{spawn_achievement_list, Achievements},
+    challenge::{spawn_challenge_info, start_challenge, Challenge, ChallengeBests},
+    consts::{QUICKSAVE, TUTORIAL_LEVEL},
+    difficulty::Difficulty,
+    level::{LevelChoice, Levels},
+    match_settings::{spawn_setting_rows, MatchSettings, SettingButton, SettingText},
+    rng::SeedSetting,
+    save::{LoadedGame, RecoveryPrompt, SaveGame},
+    scenario::{start_scenario, Campaign, CampaignProgress, ScenarioChoice},
+    scores::{spawn_score_table, HighScores},
+    tutorial::Tutorial,
+    GameState,
+};
 use iyes_loopless::prelude::*;
 
+// Which page of the main menu is showing, only meaningful while in GameState::MainMenu
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+pub enum MenuScreen {
+    None,
+    Title,
+    Map,
+    Difficulty,
+    Campaign,
+    Challenge,
+    Custom,
+    Recovery,
+    HighScores,
+    Achievements,
+}
+
 #[derive(Component)]
 pub struct Root;
 
 #[derive(Component)]
 pub struct BeginButton;
 
-pub struct Plugin;
+#[derive(Component)]
+pub struct ContinueButton;
 
-impl Plugin {
-    fn init(mut cmd: Commands, assets: Res<AssetServer>) {
-        cmd.spawn(NodeBundle {
-            style: Style {
-                size: Size {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
+#[derive(Component)]
+pub struct DifficultyButton(Difficulty);
+
+#[derive(Component)]
+pub struct HighScoresButton;
+
+#[derive(Component)]
+pub struct AchievementsButton;
+
+#[derive(Component)]
+pub struct MapButton(String);
+
+#[derive(Component)]
+pub struct EditorButton;
+
+#[derive(Component)]
+pub struct CampaignButton;
+
+#[derive(Component)]
+pub struct TutorialButton;
+
+#[derive(Component)]
+pub struct ChallengeButton;
+
+#[derive(Component)]
+pub struct StartChallengeButton;
+
+#[derive(Component)]
+pub struct CustomButton;
+
+#[derive(Component)]
+pub struct ScenarioButton(usize);
+
+// Leads to the given screen, usually back to an earlier one
+#[derive(Component)]
+pub struct BackButton(MenuScreen);
+
+#[derive(Component)]
+pub struct SeedText;
+
+// The seed as typed, only read into SeedSetting once a difficulty is picked
+#[derive(Resource, Default)]
+struct SeedInput(String);
+
+#[derive(Component)]
+pub struct RecoverButton;
+
+#[derive(Component)]
+pub struct DiscardButton;
+
+pub fn spawn_text_button(
+    parent: &mut ChildBuilder,
+    assets: &AssetServer,
+    label: &str,
+    button: impl Bundle,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size {
+                        width: Val::Px(256.0),
+                        height: Val::Px(48.0),
+                    },
+                    margin: UiRect::all(Val::Px(6.0)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
                 },
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::Center,
-                flex_direction: FlexDirection::Column,
+                background_color: Color::DARK_GRAY.into(),
                 ..default()
             },
-            background_color: Color::NONE.into(),
+            button,
+        ))
+        .with_children(|button| {
+            button.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+fn spawn_root(cmd: &mut Commands) -> Entity {
+    cmd.spawn(NodeBundle {
+        style: Style {
+            size: Size {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+            },
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            flex_direction: FlexDirection::Column,
             ..default()
-        })
-        .insert(Root)
-        .with_children(|root| {
+        },
+        background_color: Color::NONE.into(),
+        ..default()
+    })
+    .insert(Root)
+    .id()
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn open(mut cmd: Commands, recovery: Option<Res<RecoveryPrompt>>) {
+        if recovery.is_some() {
+            cmd.insert_resource(NextState(MenuScreen::Recovery));
+        } else {
+            cmd.insert_resource(NextState(MenuScreen::Title));
+        }
+    }
+
+    fn close(mut cmd: Commands) {
+        cmd.insert_resource(NextState(MenuScreen::None));
+    }
+
+    fn init_title(mut cmd: Commands, assets: Res<AssetServer>) {
+        // Every kind of game starts from here, only the custom game screen changes these
+        cmd.insert_resource(MatchSettings::default());
+
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
             root.spawn(ImageBundle {
                 image: UiImage(assets.load("title.png")),
                 style: Style {
@@ -54,13 +191,285 @@ impl Plugin {
                 },
                 BeginButton,
             ));
-            root.spawn(ImageBundle {
-                image: UiImage(assets.load("tutorial.png")),
-                ..default()
-            });
+            if SaveGame::exists(QUICKSAVE) {
+                spawn_text_button(root, &assets, "Continue", ContinueButton);
+            }
+            spawn_text_button(root, &assets, "Tutorial", TutorialButton);
+            spawn_text_button(root, &assets, "Campaign", CampaignButton);
+            spawn_text_button(root, &assets, "Daily Challenge", ChallengeButton);
+            spawn_text_button(root, &assets, "Custom Game", CustomButton);
+            spawn_text_button(root, &assets, "High Scores", HighScoresButton);
+            spawn_text_button(root, &assets, "Achievements", AchievementsButton);
+            spawn_text_button(root, &assets, "Map Editor", EditorButton);
+        });
+    }
+
+    fn init_map(mut cmd: Commands, assets: Res<AssetServer>, levels: Res<Levels>) {
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Map",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            for level in levels
+                .maps
+                .iter()
+                .filter(|level| level.file != TUTORIAL_LEVEL)
+            {
+                spawn_text_button(root, &assets, &level.name, MapButton(level.file.clone()));
+            }
+            spawn_text_button(root, &assets, "Back", BackButton(MenuScreen::Title));
+        });
+    }
+
+    fn init_campaign(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        campaign: Res<Campaign>,
+        progress: Res<CampaignProgress>,
+    ) {
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Campaign",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            for (i, scenario) in campaign.0.iter().enumerate() {
+                if !progress.is_unlocked(i) {
+                    root.spawn(TextBundle::from_section(
+                        format!("{} (locked)", scenario.name),
+                        TextStyle {
+                            font: assets.load("fonts/ModeSeven.ttf"),
+                            font_size: 20.0,
+                            color: Color::GRAY,
+                        },
+                    ));
+                    continue;
+                }
+
+                spawn_text_button(root, &assets, &scenario.name, ScenarioButton(i));
+                root.spawn(TextBundle::from_section(
+                    &scenario.briefing,
+                    TextStyle {
+                        font: assets.load("fonts/ModeSeven.ttf"),
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            }
+            spawn_text_button(root, &assets, "Back", BackButton(MenuScreen::Title));
+        });
+    }
+
+    fn init_challenge(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        levels: Res<Levels>,
+        bests: Res<ChallengeBests>,
+    ) {
+        let challenge = Challenge::today();
+        let map = &levels.get(&LevelChoice(challenge.level.clone())).name;
+
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Daily Challenge",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            spawn_challenge_info(root, &assets, &challenge, map, &bests);
+            spawn_text_button(root, &assets, "Start", StartChallengeButton);
+            spawn_text_button(root, &assets, "Back", BackButton(MenuScreen::Title));
+        });
+    }
+
+    fn init_custom(mut cmd: Commands, assets: Res<AssetServer>, settings: Res<MatchSettings>) {
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Custom Game",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            spawn_setting_rows(root, &assets, &settings);
+            spawn_text_button(root, &assets, "Choose Map", BackButton(MenuScreen::Map));
+            spawn_text_button(root, &assets, "Back", BackButton(MenuScreen::Title));
+        });
+    }
+
+    fn init_difficulty(mut cmd: Commands, assets: Res<AssetServer>, seed: Res<SeedSetting>) {
+        cmd.insert_resource(SeedInput(
+            seed.0.map(|seed| seed.to_string()).unwrap_or_default(),
+        ));
+
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Difficulty",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            root.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: assets.load("fonts/ModeSeven.ttf"),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                SeedText,
+            ));
+            for difficulty in Difficulty::ALL {
+                spawn_text_button(
+                    root,
+                    &assets,
+                    difficulty.name(),
+                    DifficultyButton(difficulty),
+                );
+            }
+            spawn_text_button(root, &assets, "Back", BackButton(MenuScreen::Map));
         });
     }
 
+    fn init_high_scores(mut cmd: Commands, assets: Res<AssetServer>, scores: Res<HighScores>) {
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "High Scores",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            spawn_score_table(root, &assets, &scores);
+            spawn_text_button(root, &assets, "Back", BackButton(MenuScreen::Title));
+        });
+    }
+
+    fn init_achievements(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        achievements: Res<Achievements>,
+    ) {
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Achievements",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            spawn_achievement_list(root, &assets, &achievements);
+            spawn_text_button(root, &assets, "Back", BackButton(MenuScreen::Title));
+        });
+    }
+
+    fn init_recovery(mut cmd: Commands, assets: Res<AssetServer>) {
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Recover game?",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            root.spawn(TextBundle::from_section(
+                "The game didn't close properly last time",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ));
+            spawn_text_button(root, &assets, "Recover", RecoverButton);
+            spawn_text_button(root, &assets, "Discard", DiscardButton);
+        });
+    }
+
+    fn handle_recovery_click(
+        mut cmd: Commands,
+        recovery: Res<RecoveryPrompt>,
+        q_button: Query<(&Interaction, Option<&RecoverButton>), Changed<Interaction>>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for (button, recover) in &q_button {
+                if button != &Interaction::Hovered {
+                    continue;
+                }
+
+                cmd.remove_resource::<RecoveryPrompt>();
+
+                match recover.and_then(|_| SaveGame::load(&recovery.0)) {
+                    Some(save) => {
+                        cmd.insert_resource(save.difficulty);
+                        cmd.insert_resource(save.level.clone());
+                        cmd.insert_resource(save.scenario.clone());
+                        cmd.insert_resource(save.challenge.clone());
+                        cmd.insert_resource(save.settings.clone());
+                        cmd.insert_resource(LoadedGame(save));
+                        cmd.insert_resource(NextState(GameState::InGame))
+                    }
+                    None => cmd.insert_resource(NextState(MenuScreen::Title)),
+                }
+            }
+        }
+    }
+
+    fn handle_seed_input(
+        mut characters: EventReader<ReceivedCharacter>,
+        keyboard: Res<Input<KeyCode>>,
+        mut input: ResMut<SeedInput>,
+    ) {
+        for character in characters.iter() {
+            if !character.char.is_ascii_digit() {
+                continue;
+            }
+
+            // Digits that would overflow the seed are left off
+            input.0.push(character.char);
+            if input.0.parse::<u64>().is_err() {
+                input.0.pop();
+            }
+        }
+
+        if keyboard.just_pressed(KeyCode::Back) {
+            input.0.pop();
+        }
+    }
+
+    fn update_seed_text(input: Res<SeedInput>, mut q_text: Query<&mut Text, With<SeedText>>) {
+        let mut text = q_text.single_mut();
+        text.sections[0].value = if input.0.is_empty() {
+            "Seed: random (type to set)".to_string()
+        } else {
+            format!("Seed: {}", input.0)
+        };
+    }
+
     fn cleanup(mut cmd: Commands, q_root: Query<Entity, With<Root>>) {
         for entity in &q_root {
             cmd.entity(entity).despawn_recursive();
@@ -75,17 +484,289 @@ impl Plugin {
         if mouse.just_released(MouseButton::Left) {
             for button in &q_button {
                 if button == &Interaction::Hovered {
+                    cmd.insert_resource(NextState(MenuScreen::Map))
+                }
+            }
+        }
+    }
+
+    fn handle_continue_click(
+        mut cmd: Commands,
+        q_button: Query<&Interaction, (Changed<Interaction>, With<ContinueButton>)>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for button in &q_button {
+                if button == &Interaction::Hovered {
+                    let Some(save) = SaveGame::load(QUICKSAVE) else { continue };
+                    cmd.insert_resource(save.difficulty);
+                    cmd.insert_resource(save.level.clone());
+                    cmd.insert_resource(save.scenario.clone());
+                    cmd.insert_resource(save.challenge.clone());
+                    cmd.insert_resource(save.settings.clone());
+                    cmd.insert_resource(LoadedGame(save));
+                    cmd.insert_resource(NextState(GameState::InGame))
+                }
+            }
+        }
+    }
+
+    fn handle_map_click(
+        mut cmd: Commands,
+        q_button: Query<(&Interaction, &MapButton), Changed<Interaction>>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for (button, MapButton(file)) in &q_button {
+                if button == &Interaction::Hovered {
+                    cmd.insert_resource(LevelChoice(file.clone()));
+                    cmd.insert_resource(ScenarioChoice(None));
+                    cmd.insert_resource(NextState(MenuScreen::Difficulty))
+                }
+            }
+        }
+    }
+
+    fn handle_difficulty_click(
+        mut cmd: Commands,
+        input: Res<SeedInput>,
+        mut seed: ResMut<SeedSetting>,
+        q_button: Query<(&Interaction, &DifficultyButton), Changed<Interaction>>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for (button, DifficultyButton(difficulty)) in &q_button {
+                if button == &Interaction::Hovered {
+                    seed.0 = input.0.parse().ok();
+                    cmd.insert_resource(*difficulty);
+                    cmd.insert_resource(NextState(GameState::InGame))
+                }
+            }
+        }
+    }
+
+    fn handle_tutorial_click(
+        mut cmd: Commands,
+        q_button: Query<&Interaction, (Changed<Interaction>, With<TutorialButton>)>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for button in &q_button {
+                if button == &Interaction::Hovered {
+                    cmd.insert_resource(Tutorial::default());
+                    cmd.insert_resource(LevelChoice(TUTORIAL_LEVEL.to_string()));
+                    cmd.insert_resource(ScenarioChoice(None));
+                    cmd.insert_resource(Difficulty::Easy);
                     cmd.insert_resource(NextState(GameState::InGame))
                 }
             }
         }
     }
+
+    fn handle_challenge_click(
+        mut cmd: Commands,
+        q_button: Query<&Interaction, (Changed<Interaction>, With<ChallengeButton>)>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for button in &q_button {
+                if button == &Interaction::Hovered {
+                    cmd.insert_resource(NextState(MenuScreen::Challenge))
+                }
+            }
+        }
+    }
+
+    fn handle_start_challenge_click(
+        mut cmd: Commands,
+        mut seed: ResMut<SeedSetting>,
+        q_button: Query<&Interaction, (Changed<Interaction>, With<StartChallengeButton>)>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for button in &q_button {
+                if button == &Interaction::Hovered {
+                    start_challenge(&mut cmd, &mut seed);
+                }
+            }
+        }
+    }
+
+    fn handle_custom_click(
+        mut cmd: Commands,
+        q_button: Query<&Interaction, (Changed<Interaction>, With<CustomButton>)>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for button in &q_button {
+                if button == &Interaction::Hovered {
+                    cmd.insert_resource(NextState(MenuScreen::Custom))
+                }
+            }
+        }
+    }
+
+    fn handle_setting_click(
+        mut settings: ResMut<MatchSettings>,
+        q_button: Query<(&Interaction, &SettingButton), Changed<Interaction>>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for (button, setting) in &q_button {
+                if button == &Interaction::Hovered {
+                    setting.apply(&mut settings);
+                }
+            }
+        }
+    }
+
+    fn update_setting_text(
+        settings: Res<MatchSettings>,
+        mut q_text: Query<(&mut Text, &SettingText)>,
+    ) {
+        if !settings.is_changed() {
+            return;
+        }
+
+        for (mut text, SettingText(setting)) in &mut q_text {
+            text.sections[0].value = setting.value(&settings);
+        }
+    }
+
+    fn handle_campaign_click(
+        mut cmd: Commands,
+        q_button: Query<&Interaction, (Changed<Interaction>, With<CampaignButton>)>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for button in &q_button {
+                if button == &Interaction::Hovered {
+                    cmd.insert_resource(NextState(MenuScreen::Campaign))
+                }
+            }
+        }
+    }
+
+    fn handle_scenario_click(
+        mut cmd: Commands,
+        campaign: Res<Campaign>,
+        q_button: Query<(&Interaction, &ScenarioButton), Changed<Interaction>>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for (button, ScenarioButton(index)) in &q_button {
+                if button == &Interaction::Hovered {
+                    start_scenario(&mut cmd, &campaign, *index);
+                }
+            }
+        }
+    }
+
+    fn handle_high_scores_click(
+        mut cmd: Commands,
+        q_button: Query<&Interaction, (Changed<Interaction>, With<HighScoresButton>)>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for button in &q_button {
+                if button == &Interaction::Hovered {
+                    cmd.insert_resource(NextState(MenuScreen::HighScores))
+                }
+            }
+        }
+    }
+
+    fn handle_achievements_click(
+        mut cmd: Commands,
+        q_button: Query<&Interaction, (Changed<Interaction>, With<AchievementsButton>)>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for button in &q_button {
+                if button == &Interaction::Hovered {
+                    cmd.insert_resource(NextState(MenuScreen::Achievements))
+                }
+            }
+        }
+    }
+
+    fn handle_editor_click(
+        mut cmd: Commands,
+        q_button: Query<&Interaction, (Changed<Interaction>, With<EditorButton>)>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for button in &q_button {
+                if button == &Interaction::Hovered {
+                    cmd.insert_resource(NextState(GameState::Editor))
+                }
+            }
+        }
+    }
+
+    fn handle_back_click(
+        mut cmd: Commands,
+        q_button: Query<(&Interaction, &BackButton), Changed<Interaction>>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for (button, BackButton(screen)) in &q_button {
+                if button == &Interaction::Hovered {
+                    cmd.insert_resource(NextState(screen.clone()))
+                }
+            }
+        }
+    }
 }
 
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut App) {
-        app.add_enter_system(GameState::MainMenu, Self::init)
-            .add_exit_system(GameState::MainMenu, Self::cleanup)
-            .add_system(Self::handle_play_click.run_in_state(GameState::MainMenu));
+        app.add_loopless_state(MenuScreen::None)
+            .init_resource::<Difficulty>()
+            .init_resource::<MatchSettings>()
+            .init_resource::<SeedInput>()
+            .add_enter_system(GameState::MainMenu, Self::open)
+            .add_exit_system(GameState::MainMenu, Self::close)
+            .add_enter_system(MenuScreen::Title, Self::init_title)
+            .add_exit_system(MenuScreen::Title, Self::cleanup)
+            .add_enter_system(MenuScreen::Map, Self::init_map)
+            .add_exit_system(MenuScreen::Map, Self::cleanup)
+            .add_enter_system(MenuScreen::Difficulty, Self::init_difficulty)
+            .add_exit_system(MenuScreen::Difficulty, Self::cleanup)
+            .add_enter_system(MenuScreen::Campaign, Self::init_campaign)
+            .add_exit_system(MenuScreen::Campaign, Self::cleanup)
+            .add_enter_system(MenuScreen::Challenge, Self::init_challenge)
+            .add_exit_system(MenuScreen::Challenge, Self::cleanup)
+            .add_enter_system(MenuScreen::Custom, Self::init_custom)
+            .add_exit_system(MenuScreen::Custom, Self::cleanup)
+            .add_enter_system(MenuScreen::Recovery, Self::init_recovery)
+            .add_exit_system(MenuScreen::Recovery, Self::cleanup)
+            .add_enter_system(MenuScreen::HighScores, Self::init_high_scores)
+            .add_exit_system(MenuScreen::HighScores, Self::cleanup)
+            .add_enter_system(MenuScreen::Achievements, Self::init_achievements)
+            .add_exit_system(MenuScreen::Achievements, Self::cleanup)
+            .add_system(Self::handle_play_click.run_in_state(MenuScreen::Title))
+            .add_system(Self::handle_continue_click.run_in_state(MenuScreen::Title))
+            .add_system(Self::handle_difficulty_click.run_in_state(MenuScreen::Difficulty))
+            .add_system(Self::handle_high_scores_click.run_in_state(MenuScreen::Title))
+            .add_system(Self::handle_map_click.run_in_state(MenuScreen::Map))
+            .add_system(Self::handle_achievements_click.run_in_state(MenuScreen::Title))
+            .add_system(Self::handle_editor_click.run_in_state(MenuScreen::Title))
+            .add_system(Self::handle_campaign_click.run_in_state(MenuScreen::Title))
+            .add_system(Self::handle_tutorial_click.run_in_state(MenuScreen::Title))
+            .add_system(Self::handle_challenge_click.run_in_state(MenuScreen::Title))
+            .add_system(Self::handle_start_challenge_click.run_in_state(MenuScreen::Challenge))
+            .add_system(Self::handle_custom_click.run_in_state(MenuScreen::Title))
+            .add_system(Self::handle_setting_click.run_in_state(MenuScreen::Custom))
+            .add_system(Self::update_setting_text.run_in_state(MenuScreen::Custom))
+            .add_system(Self::handle_scenario_click.run_in_state(MenuScreen::Campaign))
+            .add_system(Self::handle_back_click.run_in_state(GameState::MainMenu))
+            .add_system(Self::handle_seed_input.run_in_state(MenuScreen::Difficulty))
+            .add_system(Self::update_seed_text.run_in_state(MenuScreen::Difficulty))
+            .add_system(
+                Self::handle_recovery_click
+                    .run_in_state(MenuScreen::Recovery)
+                    .run_if_resource_exists::<RecoveryPrompt>(),
+            );
     }
 }
@@ -0,0 +1,410 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{
+        CARROT_COMPOST, CARROT_COST, CARROT_DECAY_TIME, CARROT_GROW_TIME, CLOVER_COMPOST,
+        CLOVER_COST, CLOVER_DECAY_TIME, CLOVER_GROW_TIME, PLOT_UNLOCK_COST, WHEAT_COMPOST,
+        WHEAT_COST, WHEAT_DECAY_TIME, WHEAT_GROW_TIME,
+    },
+    enemy::EnemyKind,
+    level::LevelPlot,
+    plot::Crop,
+};
+
+const STARTING_COMPOST: [Option<u32>; 6] =
+    [None, Some(50), Some(100), Some(200), Some(400), Some(1000)];
+const LOCKED_PLOTS: [Option<u32>; 8] = [
+    None,
+    Some(0),
+    Some(1),
+    Some(2),
+    Some(3),
+    Some(4),
+    Some(5),
+    Some(6),
+];
+const MULTIPLIERS: [f32; 7] = [0.25, 0.5, 0.75, 1.0, 1.5, 2.0, 4.0];
+
+// Tweaks for a custom game, everything at its default for any other kind of game
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchSettings {
+    // None keeps the map's own amount, scaled by difficulty
+    pub starting_compost: Option<u32>,
+    // Multiplier on how long each wave waits between spawning pests from that nest
+    pub aphid_interval: f32,
+    pub caterpillar_interval: f32,
+    pub rose_health: f32,
+    // None keeps the map's own locks
+    pub locked_plots: Option<u32>,
+    pub crop_cost: f32,
+    pub grow_time: f32,
+    pub compost_yield: f32,
+    pub unlock_cost: f32,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        MatchSettings {
+            starting_compost: None,
+            aphid_interval: 1.0,
+            caterpillar_interval: 1.0,
+            rose_health: 1.0,
+            locked_plots: None,
+            crop_cost: 1.0,
+            grow_time: 1.0,
+            compost_yield: 1.0,
+            unlock_cost: 1.0,
+        }
+    }
+}
+
+impl MatchSettings {
+    pub fn is_default(&self) -> bool {
+        *self == MatchSettings::default()
+    }
+
+    pub fn starting_compost(&self, base: u32) -> u32 {
+        self.starting_compost.unwrap_or(base)
+    }
+
+    pub fn spawn_interval(&self, kind: EnemyKind) -> f32 {
+        match kind.nest_kind() {
+            EnemyKind::Caterpillar => self.caterpillar_interval,
+            _ => self.aphid_interval,
+        }
+    }
+
+    pub fn crop_cost(&self, crop: &Crop) -> u32 {
+        let cost = match crop {
+            Crop::Carrot => CARROT_COST,
+            Crop::Clover => CLOVER_COST,
+            Crop::Wheat => WHEAT_COST,
+        };
+        (cost as f32 * self.crop_cost).round() as u32
+    }
+
+    pub fn grow_time(&self, crop: &Crop) -> f32 {
+        let time = match crop {
+            Crop::Carrot => CARROT_GROW_TIME,
+            Crop::Clover => CLOVER_GROW_TIME,
+            Crop::Wheat => WHEAT_GROW_TIME,
+        };
+        time * self.grow_time
+    }
+
+    // How long a ripe crop lasts on its plot, not adjustable but kept with the rest
+    pub fn decay_time(&self, crop: &Crop) -> f32 {
+        match crop {
+            Crop::Carrot => CARROT_DECAY_TIME,
+            Crop::Clover => CLOVER_DECAY_TIME,
+            Crop::Wheat => WHEAT_DECAY_TIME,
+        }
+    }
+
+    pub fn compost_yield(&self, crop: &Crop) -> u32 {
+        let amount = match crop {
+            Crop::Carrot => CARROT_COMPOST,
+            Crop::Clover => CLOVER_COMPOST,
+            Crop::Wheat => WHEAT_COMPOST,
+        };
+        (amount as f32 * self.compost_yield).round() as u32
+    }
+
+    pub fn unlock_cost(&self) -> u32 {
+        (PLOT_UNLOCK_COST as f32 * self.unlock_cost).round() as u32
+    }
+
+    // Whether each of the map's plots starts locked. The map's own open plots are the last
+    // to be locked, so there's still somewhere sensible to start from
+    pub fn locked_plots(&self, plots: &[LevelPlot]) -> Vec<bool> {
+        let Some(count) = self.locked_plots else { return plots.iter().map(|plot| plot.locked).collect() };
+
+        let mut order = (0..plots.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| plots[*i].locked);
+
+        let mut locked = vec![true; plots.len()];
+        for i in order
+            .into_iter()
+            .take(plots.len().saturating_sub(count as usize))
+        {
+            locked[i] = false;
+        }
+        locked
+    }
+}
+
+// One row of the custom game screen
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    StartingCompost,
+    AphidInterval,
+    CaterpillarInterval,
+    RoseHealth,
+    LockedPlots,
+    CropCost,
+    GrowTime,
+    CompostYield,
+    UnlockCost,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 9] = [
+        Setting::StartingCompost,
+        Setting::AphidInterval,
+        Setting::CaterpillarInterval,
+        Setting::RoseHealth,
+        Setting::LockedPlots,
+        Setting::CropCost,
+        Setting::GrowTime,
+        Setting::CompostYield,
+        Setting::UnlockCost,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Setting::StartingCompost => "Starting compost",
+            Setting::AphidInterval => "Aphid spawn interval",
+            Setting::CaterpillarInterval => "Caterpillar spawn interval",
+            Setting::RoseHealth => "Rose health",
+            Setting::LockedPlots => "Locked plots",
+            Setting::CropCost => "Crop cost",
+            Setting::GrowTime => "Grow time",
+            Setting::CompostYield => "Compost yield",
+            Setting::UnlockCost => "Plot unlock cost",
+        }
+    }
+
+    pub fn value(&self, settings: &MatchSettings) -> String {
+        let multiplier = |value: f32| format!("{}x", value);
+        let or_map = |value: Option<u32>| value.map_or("Map".to_string(), |v| v.to_string());
+
+        match self {
+            Setting::StartingCompost => or_map(settings.starting_compost),
+            Setting::AphidInterval => multiplier(settings.aphid_interval),
+            Setting::CaterpillarInterval => multiplier(settings.caterpillar_interval),
+            Setting::RoseHealth => multiplier(settings.rose_health),
+            Setting::LockedPlots => or_map(settings.locked_plots),
+            Setting::CropCost => multiplier(settings.crop_cost),
+            Setting::GrowTime => multiplier(settings.grow_time),
+            Setting::CompostYield => multiplier(settings.compost_yield),
+            Setting::UnlockCost => multiplier(settings.unlock_cost),
+        }
+    }
+
+    // Moves to the next or previous option, stopping at either end
+    pub fn step(&self, settings: &mut MatchSettings, forward: bool) {
+        match self {
+            Setting::StartingCompost => {
+                step(&STARTING_COMPOST, &mut settings.starting_compost, forward)
+            }
+            Setting::AphidInterval => step(&MULTIPLIERS, &mut settings.aphid_interval, forward),
+            Setting::CaterpillarInterval => {
+                step(&MULTIPLIERS, &mut settings.caterpillar_interval, forward)
+            }
+            Setting::RoseHealth => step(&MULTIPLIERS, &mut settings.rose_health, forward),
+            Setting::LockedPlots => step(&LOCKED_PLOTS, &mut settings.locked_plots, forward),
+            Setting::CropCost => step(&MULTIPLIERS, &mut settings.crop_cost, forward),
+            Setting::GrowTime => step(&MULTIPLIERS, &mut settings.grow_time, forward),
+            Setting::CompostYield => step(&MULTIPLIERS, &mut settings.compost_yield, forward),
+            Setting::UnlockCost => step(&MULTIPLIERS, &mut settings.unlock_cost, forward),
+        }
+    }
+}
+
+fn step<T: Copy + PartialEq>(options: &[T], value: &mut T, forward: bool) {
+    let current = options
+        .iter()
+        .position(|option| option == value)
+        .unwrap_or(0);
+    let next = if forward {
+        (current + 1).min(options.len() - 1)
+    } else {
+        current.saturating_sub(1)
+    };
+    *value = options[next];
+}
+
+#[derive(Component)]
+pub struct SettingButton {
+    setting: Setting,
+    forward: bool,
+}
+
+#[derive(Component)]
+pub struct SettingText(pub Setting);
+
+impl SettingButton {
+    pub fn apply(&self, settings: &mut MatchSettings) {
+        self.setting.step(settings, self.forward);
+    }
+}
+
+fn spawn_arrow_button(
+    parent: &mut ChildBuilder,
+    assets: &AssetServer,
+    setting: Setting,
+    forward: bool,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size {
+                        width: Val::Px(32.0),
+                        height: Val::Px(32.0),
+                    },
+                    margin: UiRect::all(Val::Px(4.0)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::DARK_GRAY.into(),
+                ..default()
+            },
+            SettingButton { setting, forward },
+        ))
+        .with_children(|button| {
+            button.spawn(TextBundle::from_section(
+                if forward { ">" } else { "<" },
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+pub fn spawn_setting_rows(
+    parent: &mut ChildBuilder,
+    assets: &AssetServer,
+    settings: &MatchSettings,
+) {
+    for setting in Setting::ALL {
+        parent
+            .spawn(NodeBundle {
+                style: Style {
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|row| {
+                row.spawn(
+                    TextBundle::from_section(
+                        setting.name(),
+                        TextStyle {
+                            font: assets.load("fonts/ModeSeven.ttf"),
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                        },
+                    )
+                    .with_style(Style {
+                        size: Size {
+                            width: Val::Px(320.0),
+                            height: Val::Auto,
+                        },
+                        ..default()
+                    }),
+                );
+                spawn_arrow_button(row, assets, setting, false);
+                row.spawn((
+                    TextBundle::from_section(
+                        setting.value(settings),
+                        TextStyle {
+                            font: assets.load("fonts/ModeSeven.ttf"),
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                        },
+                    )
+                    .with_style(Style {
+                        size: Size {
+                            width: Val::Px(64.0),
+                            height: Val::Auto,
+                        },
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    }),
+                    SettingText(setting),
+                ));
+                spawn_arrow_button(row, assets, setting, true);
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plots(locked: &[bool]) -> Vec<LevelPlot> {
+        locked
+            .iter()
+            .map(|locked| LevelPlot {
+                pos: Vec2::ZERO,
+                locked: *locked,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn map_locks_are_kept_by_default() {
+        let map = [false, true, false, true];
+        assert_eq!(MatchSettings::default().locked_plots(&plots(&map)), map);
+    }
+
+    #[test]
+    fn open_plots_are_the_last_to_lock() {
+        let map = plots(&[true, false, true, false]);
+        let locked = |count| {
+            MatchSettings {
+                locked_plots: Some(count),
+                ..default()
+            }
+            .locked_plots(&map)
+        };
+
+        assert_eq!(locked(0), [false, false, false, false]);
+        assert_eq!(locked(1), [false, false, true, false]);
+        assert_eq!(locked(2), [true, false, true, false]);
+        assert_eq!(locked(3), [true, false, true, true]);
+        // More locks than plots just locks everything
+        assert_eq!(locked(6), [true, true, true, true]);
+    }
+
+    #[test]
+    fn stepping_stops_at_either_end() {
+        let mut settings = MatchSettings::default();
+
+        Setting::GrowTime.step(&mut settings, true);
+        assert_eq!(settings.grow_time, 1.5);
+        for _ in 0..MULTIPLIERS.len() {
+            Setting::GrowTime.step(&mut settings, true);
+        }
+        assert_eq!(settings.grow_time, 4.0);
+        for _ in 0..MULTIPLIERS.len() {
+            Setting::GrowTime.step(&mut settings, false);
+        }
+        assert_eq!(settings.grow_time, 0.25);
+
+        Setting::LockedPlots.step(&mut settings, false);
+        assert_eq!(settings.locked_plots, None);
+        Setting::LockedPlots.step(&mut settings, true);
+        assert_eq!(settings.locked_plots, Some(0));
+    }
+
+    #[test]
+    fn stepping_back_to_every_default_is_default_again() {
+        let mut settings = MatchSettings::default();
+        for setting in Setting::ALL {
+            setting.step(&mut settings, true);
+        }
+        assert!(!settings.is_default());
+
+        for setting in Setting::ALL {
+            setting.step(&mut settings, false);
+        }
+        assert!(settings.is_default());
+    }
+}
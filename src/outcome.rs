@@ -0,0 +1,235 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    enemy::Nest,
+    faction::Faction,
+    game::{Compost, Rose},
+    health::Dead,
+    main_menu::spawn_text_button,
+    pause::{self, spawn_root, MatchState, PauseButton},
+    scenario::{spawn_objectives, Campaign, NextScenarioButton, ScenarioChoice},
+    scores::{spawn_score_table, HighScores},
+    stats::{spawn_stats, MatchStats},
+    waves::WaveDirector,
+    GameState,
+};
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    Stats,
+    Scores,
+}
+
+#[derive(Component)]
+pub struct TabButton(Tab);
+
+#[derive(Component)]
+pub struct Panel(Tab);
+
+// The stats and the high-score table share the space below the headline
+fn spawn_tabs(
+    parent: &mut ChildBuilder,
+    assets: &AssetServer,
+    stats: &MatchStats,
+    compost: &Compost,
+    scores: &HighScores,
+) {
+    parent.spawn(NodeBundle::default()).with_children(|row| {
+        spawn_text_button(row, assets, "Stats", TabButton(Tab::Stats));
+        spawn_text_button(row, assets, "High Scores", TabButton(Tab::Scores));
+    });
+
+    for tab in [Tab::Stats, Tab::Scores] {
+        parent
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        display: if tab == Tab::Stats {
+                            Display::Flex
+                        } else {
+                            Display::None
+                        },
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                },
+                Panel(tab),
+            ))
+            .with_children(|panel| match tab {
+                Tab::Stats => spawn_stats(panel, assets, stats, compost),
+                Tab::Scores => spawn_score_table(panel, assets, scores),
+            });
+    }
+}
+
+fn format_time(director: &WaveDirector) -> String {
+    let seconds = director.total.as_secs();
+    format!("{:<02}:{:<02}", seconds / 60, seconds % 60)
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn check_outcome(
+        mut cmd: Commands,
+        director: Res<WaveDirector>,
+        scenario: Res<ScenarioChoice>,
+        q_rose: Query<(), With<Rose>>,
+        q_pests: Query<&Faction, (Without<Nest>, Without<Dead>)>,
+    ) {
+        if q_rose.is_empty() {
+            cmd.insert_resource(NextState(MatchState::GameOver));
+            return;
+        }
+
+        // Scenarios are won on their objectives instead. Nests regrow, so only the pests
+        // roaming the garden have to be dealt with
+        if scenario.0.is_none()
+            && director.finished
+            && !q_pests.iter().any(|faction| faction == &Faction::Pests)
+        {
+            cmd.insert_resource(NextState(MatchState::Victory));
+        }
+    }
+
+    fn handle_tab_click(
+        q_button: Query<(&Interaction, &TabButton), Changed<Interaction>>,
+        mut q_panel: Query<(&Panel, &mut Style)>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if !mouse.just_released(MouseButton::Left) {
+            return;
+        }
+
+        for (interaction, TabButton(tab)) in &q_button {
+            if interaction != &Interaction::Hovered {
+                continue;
+            }
+
+            for (Panel(panel), mut style) in &mut q_panel {
+                style.display = if panel == tab {
+                    Display::Flex
+                } else {
+                    Display::None
+                };
+            }
+        }
+    }
+
+    fn init_game_over(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        director: Res<WaveDirector>,
+        scores: Res<HighScores>,
+        stats: Res<MatchStats>,
+        compost: Res<Compost>,
+        (campaign, choice): (Res<Campaign>, Res<ScenarioChoice>),
+    ) {
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(ImageBundle {
+                style: Style {
+                    size: Size {
+                        width: Val::Px(512.0),
+                        height: Val::Px(128.0),
+                    },
+                    ..default()
+                },
+                image: UiImage(assets.load("death_text.png")),
+                ..default()
+            });
+            root.spawn(TextBundle::from_section(
+                format!(
+                    "Survived {} and reached wave {}",
+                    format_time(&director),
+                    director.wave
+                ),
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            ));
+            if let Some(scenario) = choice.get(&campaign) {
+                spawn_objectives(root, &assets, scenario, &stats, &director);
+            }
+            spawn_tabs(root, &assets, &stats, &compost, &scores);
+            spawn_text_button(root, &assets, "Restart", PauseButton::Restart);
+            spawn_text_button(root, &assets, "Quit to Menu", PauseButton::Quit);
+        });
+    }
+
+    fn init_victory(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        director: Res<WaveDirector>,
+        scores: Res<HighScores>,
+        stats: Res<MatchStats>,
+        compost: Res<Compost>,
+        (campaign, choice): (Res<Campaign>, Res<ScenarioChoice>),
+    ) {
+        let scenario = choice.get(&campaign);
+
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Victory!",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            root.spawn(TextBundle::from_section(
+                match scenario {
+                    Some(scenario) => {
+                        format!("{} complete in {}", scenario.name, format_time(&director))
+                    }
+                    None => format!(
+                        "The garden held off {} waves in {}",
+                        director.wave,
+                        format_time(&director)
+                    ),
+                },
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            ));
+            if let Some(scenario) = scenario {
+                spawn_objectives(root, &assets, scenario, &stats, &director);
+            }
+            spawn_tabs(root, &assets, &stats, &compost, &scores);
+            if choice.0.is_some_and(|index| index + 1 < campaign.0.len()) {
+                spawn_text_button(root, &assets, "Next Scenario", NextScenarioButton);
+            }
+            spawn_text_button(root, &assets, "Restart", PauseButton::Restart);
+            spawn_text_button(root, &assets, "Quit to Menu", PauseButton::Quit);
+        });
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_enter_system(
+            MatchState::GameOver,
+            Self::init_game_over.after("record_score"),
+        )
+        .add_exit_system(MatchState::GameOver, pause::Plugin::cleanup)
+        .add_enter_system(
+            MatchState::Victory,
+            Self::init_victory.after("record_score"),
+        )
+        .add_exit_system(MatchState::Victory, pause::Plugin::cleanup)
+        .add_system(Self::handle_tab_click.run_in_state(GameState::InGame))
+        .add_system(
+            Self::check_outcome
+                .run_in_state(GameState::InGame)
+                .run_in_state(MatchState::Running),
+        );
+    }
+}
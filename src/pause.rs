@@ -0,0 +1,254 @@
+use bevy::prelude::*;
+use iyes_loopless::{fixedtimestep::FixedTimesteps, prelude::*};
+
+use crate::{main_menu::spawn_text_button, settings::Settings, sim::SIM, GameState};
+
+// Whether the match is being played, only meaningful while in GameState::InGame.
+// The simulation only ticks while Running, menus and the rest of the UI keep going
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+pub enum MatchState {
+    Running,
+    Paused,
+    Settings,
+    GameOver,
+    Victory,
+}
+
+#[derive(Component)]
+pub struct Root;
+
+#[derive(Component)]
+pub enum PauseButton {
+    Resume,
+    Restart,
+    Settings,
+    Quit,
+}
+
+#[derive(Component)]
+pub enum SettingsButton {
+    FocusPause,
+    Back,
+}
+
+pub fn spawn_root(cmd: &mut Commands) -> Entity {
+    cmd.spawn(NodeBundle {
+        style: Style {
+            size: Size {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+            },
+            position_type: PositionType::Absolute,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+        z_index: ZIndex::Global(10),
+        ..default()
+    })
+    .insert(Root)
+    .id()
+}
+
+fn focus_pause_label(settings: &Settings) -> String {
+    let value = if settings.pause_on_focus_loss {
+        "On"
+    } else {
+        "Off"
+    };
+    format!("Pause on focus loss: {}", value)
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn freeze(mut timesteps: ResMut<FixedTimesteps>) {
+        if let Some(sim) = timesteps.get_mut(SIM) {
+            sim.pause();
+        }
+    }
+
+    fn unfreeze(mut timesteps: ResMut<FixedTimesteps>) {
+        if let Some(sim) = timesteps.get_mut(SIM) {
+            sim.unpause();
+        }
+    }
+
+    fn resume(mut cmd: Commands) {
+        cmd.insert_resource(NextState(MatchState::Running));
+    }
+
+    fn toggle_pause(
+        mut cmd: Commands,
+        keyboard: Res<Input<KeyCode>>,
+        state: Res<CurrentState<MatchState>>,
+    ) {
+        if !keyboard.just_pressed(KeyCode::Escape) {
+            return;
+        }
+
+        let next = match state.0 {
+            MatchState::Running => MatchState::Paused,
+            MatchState::Paused => MatchState::Running,
+            MatchState::Settings => MatchState::Paused,
+            MatchState::GameOver | MatchState::Victory => return,
+        };
+        cmd.insert_resource(NextState(next));
+    }
+
+    fn pause_on_lost_focus(mut cmd: Commands, settings: Res<Settings>, windows: Res<Windows>) {
+        if settings.pause_on_focus_loss && !windows.primary().is_focused() {
+            cmd.insert_resource(NextState(MatchState::Paused));
+        }
+    }
+
+    fn init_paused(mut cmd: Commands, assets: Res<AssetServer>) {
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Paused",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            spawn_text_button(root, &assets, "Resume", PauseButton::Resume);
+            spawn_text_button(root, &assets, "Restart", PauseButton::Restart);
+            spawn_text_button(root, &assets, "Settings", PauseButton::Settings);
+            spawn_text_button(root, &assets, "Quit to Menu", PauseButton::Quit);
+        });
+    }
+
+    fn init_settings(mut cmd: Commands, assets: Res<AssetServer>, settings: Res<Settings>) {
+        let root = spawn_root(&mut cmd);
+        cmd.entity(root).with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Settings",
+                TextStyle {
+                    font: assets.load("fonts/ModeSeven.ttf"),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            root.spawn((
+                ButtonBundle {
+                    style: Style {
+                        size: Size {
+                            width: Val::Px(512.0),
+                            height: Val::Px(48.0),
+                        },
+                        margin: UiRect::all(Val::Px(6.0)),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    background_color: Color::DARK_GRAY.into(),
+                    ..default()
+                },
+                SettingsButton::FocusPause,
+            ))
+            .with_children(|button| {
+                button.spawn(TextBundle::from_section(
+                    focus_pause_label(&settings),
+                    TextStyle {
+                        font: assets.load("fonts/ModeSeven.ttf"),
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            });
+            spawn_text_button(root, &assets, "Back", SettingsButton::Back);
+        });
+    }
+
+    pub fn cleanup(mut cmd: Commands, q_root: Query<Entity, With<Root>>) {
+        for entity in &q_root {
+            cmd.entity(entity).despawn_recursive();
+        }
+    }
+
+    fn handle_paused_click(
+        mut cmd: Commands,
+        q_button: Query<(&Interaction, &PauseButton), Changed<Interaction>>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if !mouse.just_released(MouseButton::Left) {
+            return;
+        }
+
+        for (interaction, button) in &q_button {
+            if interaction != &Interaction::Hovered {
+                continue;
+            }
+
+            match button {
+                PauseButton::Resume => cmd.insert_resource(NextState(MatchState::Running)),
+                // Re-entering the state runs its exit and enter systems, starting a fresh game
+                PauseButton::Restart => cmd.insert_resource(NextState(GameState::InGame)),
+                PauseButton::Settings => cmd.insert_resource(NextState(MatchState::Settings)),
+                PauseButton::Quit => cmd.insert_resource(NextState(GameState::MainMenu)),
+            }
+        }
+    }
+
+    fn handle_settings_click(
+        mut cmd: Commands,
+        mut settings: ResMut<Settings>,
+        q_button: Query<(&Interaction, &SettingsButton, &Children), Changed<Interaction>>,
+        mut q_text: Query<&mut Text>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if !mouse.just_released(MouseButton::Left) {
+            return;
+        }
+
+        for (interaction, button, children) in &q_button {
+            if interaction != &Interaction::Hovered {
+                continue;
+            }
+
+            match button {
+                SettingsButton::FocusPause => {
+                    settings.pause_on_focus_loss = !settings.pause_on_focus_loss;
+
+                    for child in children.iter() {
+                        if let Ok(mut text) = q_text.get_mut(*child) {
+                            text.sections[0].value = focus_pause_label(&settings);
+                        }
+                    }
+                }
+                SettingsButton::Back => cmd.insert_resource(NextState(MatchState::Paused)),
+            }
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_loopless_state(MatchState::Running)
+            .add_enter_system(GameState::InGame, Self::resume)
+            .add_exit_system(GameState::InGame, Self::resume)
+            .add_enter_system(MatchState::Running, Self::unfreeze)
+            .add_exit_system(MatchState::Running, Self::freeze)
+            .add_enter_system(MatchState::Paused, Self::init_paused)
+            .add_exit_system(MatchState::Paused, Self::cleanup)
+            .add_enter_system(MatchState::Settings, Self::init_settings)
+            .add_exit_system(MatchState::Settings, Self::cleanup)
+            .add_system(Self::toggle_pause.run_in_state(GameState::InGame))
+            .add_system(
+                Self::pause_on_lost_focus
+                    .run_in_state(GameState::InGame)
+                    .run_in_state(MatchState::Running),
+            )
+            // Also drives the restart and quit buttons of the game over and victory screens
+            .add_system(Self::handle_paused_click.run_in_state(GameState::InGame))
+            .add_system(
+                Self::handle_settings_click
+                    .run_in_state(GameState::InGame)
+                    .run_in_state(MatchState::Settings),
+            );
+    }
+}
@@ -3,21 +3,24 @@ use std::f32::consts::TAU;
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    consts::{
-        CARROT_COMPOST, CARROT_COST, CARROT_DECAY_TIME, CARROT_GROW_TIME, CLOVER_COMPOST,
-        CLOVER_COST, CLOVER_DECAY_TIME, CLOVER_GROW_TIME, PLOT_CIRCLE_BUTTON_RADIUS,
-        PLOT_CIRCLE_RADIUS, PLOT_COLLISION_GROUP, PLOT_SIZE, PLOT_UNLOCK_COST, WHEAT_COMPOST,
-        WHEAT_COST, WHEAT_DECAY_TIME, WHEAT_GROW_TIME,
-    },
+    challenge::ChallengeChoice,
+    consts::{PLOT_CIRCLE_BUTTON_RADIUS, PLOT_CIRCLE_RADIUS, PLOT_COLLISION_GROUP, PLOT_SIZE},
     game::Compost,
+    level::{LevelChoice, Levels},
+    match_settings::MatchSettings,
+    pause::MatchState,
+    replay::{PendingActions, PlayerAction, SimId},
     selection::Selectable,
+    sim::{FrameStage, SimTime, SIM, SIM_COMMANDS},
+    stats::MatchStats,
     utils::{MousePosition, PlaySound},
     GameState,
 };
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone, Serialize, Deserialize)]
 pub enum Plot {
     #[default]
     Locked,
@@ -29,13 +32,25 @@ pub enum Plot {
 #[derive(Component)]
 pub struct PlotOverlay;
 
-#[derive(Debug, Clone, PartialEq, Component)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Component, Serialize, Deserialize)]
 pub enum Crop {
     Carrot,
     Clover,
     Wheat,
 }
 
+impl Crop {
+    pub const ALL: [Crop; 3] = [Crop::Carrot, Crop::Clover, Crop::Wheat];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Crop::Carrot => "Carrots",
+            Crop::Clover => "Clover",
+            Crop::Wheat => "Wheat",
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct PlotCircle {
     target: Entity,
@@ -51,8 +66,8 @@ pub struct PlotCircleButton {
 
 struct ActivatePlotCircle(Entity);
 
-#[derive(Debug, Clone)]
-enum PlotAction {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PlotAction {
     Plant(Crop),
     Harvest(Crop),
     Cancel,
@@ -60,6 +75,11 @@ enum PlotAction {
     Unlock,
 }
 
+pub struct PlotEvent {
+    pub plot: Entity,
+    pub action: PlotAction,
+}
+
 pub struct HarvestEvent {
     pub crop: Crop,
     pub pos: Vec3,
@@ -74,12 +94,16 @@ pub struct CompostDisplayText;
 pub struct Plugin;
 
 impl Plugin {
-    fn init(mut cmd: Commands, assets: Res<AssetServer>) {
-        for offset in [
-            Vec2::new(1.0, 0.0),
-            Vec2::new(0.0, 1.0),
-            Vec2::new(-1.0, 0.0),
-        ] {
+    fn init(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        levels: Res<Levels>,
+        choice: Res<LevelChoice>,
+        settings: Res<MatchSettings>,
+    ) {
+        let plots = &levels.get(&choice).plots;
+
+        for (plot, locked) in plots.iter().zip(settings.locked_plots(plots)) {
             cmd.spawn((
                 SpriteBundle {
                     texture: assets.load("plot.png"),
@@ -88,14 +112,10 @@ impl Plugin {
                         ..default()
                     },
 
-                    transform: Transform::from_translation(Vec3::new(
-                        offset.x * PLOT_SIZE.x,
-                        offset.y * PLOT_SIZE.y,
-                        0.0,
-                    )),
+                    transform: Transform::from_translation(plot.pos.extend(0.0)),
                     ..default()
                 },
-                Plot::default(),
+                if locked { Plot::Locked } else { Plot::Empty },
                 Collider::cuboid(PLOT_SIZE.x / 2.0, PLOT_SIZE.y / 2.0),
                 Sensor,
                 CollisionGroups {
@@ -122,43 +142,6 @@ impl Plugin {
                 ));
             });
         }
-        cmd.spawn((
-            SpriteBundle {
-                texture: assets.load("plot.png"),
-                sprite: Sprite {
-                    custom_size: Some(PLOT_SIZE),
-                    ..default()
-                },
-
-                transform: Transform::from_translation(Vec3::new(0.0, -PLOT_SIZE.y, 0.0)),
-                ..default()
-            },
-            Plot::Empty,
-            Collider::cuboid(PLOT_SIZE.x / 2.0, PLOT_SIZE.y / 2.0),
-            Sensor,
-            CollisionGroups {
-                memberships: PLOT_COLLISION_GROUP,
-                filters: Group::NONE,
-            },
-        ))
-        .with_children(|parent| {
-            parent.spawn((
-                SpriteBundle {
-                    texture: assets.load("empty.png"),
-                    transform: Transform::from_translation(Vec3::new(
-                        0.0,
-                        -PLOT_SIZE.y / 2.0,
-                        0.0001,
-                    )),
-                    sprite: Sprite {
-                        anchor: bevy::sprite::Anchor::BottomCenter,
-                        ..default()
-                    },
-                    ..default()
-                },
-                PlotOverlay,
-            ));
-        });
     }
 
     fn plot_click(
@@ -195,10 +178,11 @@ impl Plugin {
         assets: Res<AssetServer>,
         mut plot_circle: ResMut<ActivePlotCircle>,
         mut ev_plot: EventReader<ActivatePlotCircle>,
+        challenge: Res<ChallengeChoice>,
         q_plot: Query<(&Plot, &GlobalTransform)>,
     ) {
         if let Some(ActivatePlotCircle(e)) = ev_plot.iter().last() {
-            let Ok((plot, pos)) = q_plot.get(*e) else { return; };
+            let Ok((plot, pos)) = q_plot.get(*e) else { return };
             let mut pos = pos.translation();
             pos.z = 50.0;
             let new_plot_circle = cmd
@@ -277,35 +261,47 @@ impl Plugin {
                             ));
                             // clover
 
-                            v.spawn((
-                                SpriteBundle {
-                                    texture: assets.load("plant_clover.png"),
-                                    transform: Transform::from_translation(Vec3 {
-                                        x: f32::cos(7.0 * TAU / 12.0) * PLOT_CIRCLE_RADIUS * 0.75,
-                                        y: f32::sin(7.0 * TAU / 12.0) * PLOT_CIRCLE_RADIUS * 0.75,
-                                        z: 0.1,
-                                    }),
-                                    ..default()
-                                },
-                                PlotCircleButton {
-                                    action: PlotAction::Plant(Crop::Clover),
-                                },
-                            ));
+                            if challenge.allows(&Crop::Clover) {
+                                v.spawn((
+                                    SpriteBundle {
+                                        texture: assets.load("plant_clover.png"),
+                                        transform: Transform::from_translation(Vec3 {
+                                            x: f32::cos(7.0 * TAU / 12.0)
+                                                * PLOT_CIRCLE_RADIUS
+                                                * 0.75,
+                                            y: f32::sin(7.0 * TAU / 12.0)
+                                                * PLOT_CIRCLE_RADIUS
+                                                * 0.75,
+                                            z: 0.1,
+                                        }),
+                                        ..default()
+                                    },
+                                    PlotCircleButton {
+                                        action: PlotAction::Plant(Crop::Clover),
+                                    },
+                                ));
+                            }
                             // wheat
-                            v.spawn((
-                                SpriteBundle {
-                                    texture: assets.load("plant_wheat.png"),
-                                    transform: Transform::from_translation(Vec3 {
-                                        x: f32::cos(11.0 * TAU / 12.0) * PLOT_CIRCLE_RADIUS * 0.75,
-                                        y: f32::sin(11.0 * TAU / 12.0) * PLOT_CIRCLE_RADIUS * 0.75,
-                                        z: 0.1,
-                                    }),
-                                    ..default()
-                                },
-                                PlotCircleButton {
-                                    action: PlotAction::Plant(Crop::Wheat),
-                                },
-                            ));
+                            if challenge.allows(&Crop::Wheat) {
+                                v.spawn((
+                                    SpriteBundle {
+                                        texture: assets.load("plant_wheat.png"),
+                                        transform: Transform::from_translation(Vec3 {
+                                            x: f32::cos(11.0 * TAU / 12.0)
+                                                * PLOT_CIRCLE_RADIUS
+                                                * 0.75,
+                                            y: f32::sin(11.0 * TAU / 12.0)
+                                                * PLOT_CIRCLE_RADIUS
+                                                * 0.75,
+                                            z: 0.1,
+                                        }),
+                                        ..default()
+                                    },
+                                    PlotCircleButton {
+                                        action: PlotAction::Plant(Crop::Wheat),
+                                    },
+                                ));
+                            }
                         }
                         Plot::Growing(_, _) => {
                             v.spawn((
@@ -391,21 +387,32 @@ impl Plugin {
 
     fn plot_button_click(
         mut cmd: Commands,
-        mut plot_event: EventWriter<PlotAction>,
+        mut pending: ResMut<PendingActions>,
+        mut plot_circle: ResMut<ActivePlotCircle>,
         mouse_pos: Res<MousePosition>,
         mouse_button: Res<Input<MouseButton>>,
-        q_plot_circle_button: Query<(Entity, &GlobalTransform, &PlotCircleButton)>,
+        q_plot_circle_button: Query<(&GlobalTransform, &PlotCircleButton)>,
+        q_plot_circle: Query<&PlotCircle>,
+        q_plot: Query<&SimId, With<Plot>>,
     ) {
         if mouse_button.just_released(MouseButton::Left) {
-            for (entity, transform, button) in &q_plot_circle_button {
+            for (transform, button) in &q_plot_circle_button {
                 let dist = transform
                     .translation()
                     .truncate()
                     .distance(mouse_pos.truncate());
 
                 if dist <= PLOT_CIRCLE_BUTTON_RADIUS {
-                    cmd.entity(entity).remove::<PlotCircleButton>();
-                    plot_event.send(button.action.clone());
+                    let Some(circle) = plot_circle.0.take() else { return };
+                    cmd.entity(circle).despawn_recursive();
+
+                    let Ok(target) = q_plot_circle.get(circle).map(|v| v.target) else { return };
+                    let Ok(plot) = q_plot.get(target) else { return };
+
+                    pending.0.push(PlayerAction::Plot {
+                        plot: *plot,
+                        action: button.action.clone(),
+                    });
                     return;
                 }
             }
@@ -417,6 +424,7 @@ impl Plugin {
         mouse_pos: Res<MousePosition>,
         mut q_compost_display: Query<&mut Visibility, With<CompostDisplay>>,
         mut q_compost_display_text: Query<&mut Text, With<CompostDisplayText>>,
+        settings: Res<MatchSettings>,
     ) {
         for (transform, button) in &q_plot_circle_button {
             let dist = transform
@@ -429,11 +437,7 @@ impl Plugin {
                     PlotAction::Plant(crop) => {
                         q_compost_display.single_mut().is_visible = true;
                         let mut text = q_compost_display_text.single_mut();
-                        let number = match crop {
-                            Crop::Carrot => CARROT_COST,
-                            Crop::Clover => CLOVER_COST,
-                            Crop::Wheat => WHEAT_COST,
-                        };
+                        let number = settings.crop_cost(crop);
 
                         text.sections[0].value = format!("{number}");
                         text.sections[0].style.color = Color::RED;
@@ -441,11 +445,7 @@ impl Plugin {
                     PlotAction::Compost(crop) => {
                         q_compost_display.single_mut().is_visible = true;
                         let mut text = q_compost_display_text.single_mut();
-                        let number = match crop {
-                            Crop::Carrot => CARROT_COMPOST,
-                            Crop::Clover => CLOVER_COMPOST,
-                            Crop::Wheat => WHEAT_COMPOST,
-                        };
+                        let number = settings.compost_yield(crop);
 
                         text.sections[0].value = format!("{number}");
                         text.sections[0].style.color = Color::GREEN;
@@ -454,7 +454,7 @@ impl Plugin {
                         q_compost_display.single_mut().is_visible = true;
                         let mut text = q_compost_display_text.single_mut();
 
-                        text.sections[0].value = format!("{PLOT_UNLOCK_COST}");
+                        text.sections[0].value = format!("{}", settings.unlock_cost());
                         text.sections[0].style.color = Color::RED;
                     }
                     _ => {}
@@ -469,59 +469,50 @@ impl Plugin {
     }
 
     fn handle_plot_event(
-        mut cmd: Commands,
-        mut plot_events: ResMut<Events<PlotAction>>,
+        mut plot_events: ResMut<Events<PlotEvent>>,
         mut harvest_events: EventWriter<HarvestEvent>,
         mut sound_events: EventWriter<PlaySound>,
         mut compost: ResMut<Compost>,
-        mut plot_circle: ResMut<ActivePlotCircle>,
-        q_plot_circle: Query<&PlotCircle>,
+        mut stats: ResMut<MatchStats>,
+        (challenge, settings): (Res<ChallengeChoice>, Res<MatchSettings>),
         mut q_plots: Query<(&mut Plot, &GlobalTransform)>,
     ) {
-        for event in plot_events.drain() {
-            let Some(plot_circle) = plot_circle.0.take() else { return };
-            cmd.entity(plot_circle).despawn_recursive();
-
-            let Ok(target) = q_plot_circle.get(plot_circle).map(|v| v.target) else { return };
-
-            let Ok((mut plot, transform)) = q_plots.get_mut(target) else { return };
-
+        for PlotEvent {
+            plot,
+            action: event,
+        } in plot_events.drain()
+        {
+            let Ok((mut plot, transform)) = q_plots.get_mut(plot) else { continue };
+
+            // Actions wait for the next tick, by then the plot may have moved on without them
             match &*plot {
                 Plot::Locked => match event {
                     PlotAction::Unlock => {
-                        if compost.0 >= PLOT_UNLOCK_COST {
+                        if compost.spend(settings.unlock_cost()) {
                             *plot = Plot::Empty;
-                            compost.0 -= PLOT_UNLOCK_COST;
+                            stats.plots_unlocked += 1;
                             sound_events.send(PlaySound("clear.ogg".to_owned()));
                         }
                     }
-                    _ => {
-                        unreachable!()
-                    }
+                    _ => continue,
                 },
                 Plot::Empty => match event {
                     PlotAction::Plant(crop) => {
-                        let cost = match crop {
-                            Crop::Carrot => CARROT_COST,
-                            Crop::Clover => CLOVER_COST,
-                            Crop::Wheat => WHEAT_COST,
-                        };
+                        if !challenge.allows(&crop) {
+                            continue;
+                        }
 
-                        if compost.0 >= cost {
+                        if compost.spend(settings.crop_cost(&crop)) {
+                            *stats.planted.entry(crop.clone()).or_default() += 1;
                             *plot = Plot::Growing(crop, 0.0);
-                            compost.0 -= cost;
                             sound_events.send(PlaySound("plant.ogg".to_owned()));
                         }
                     }
-                    _ => {
-                        unreachable!();
-                    }
+                    _ => continue,
                 },
                 Plot::Growing(_, _) => match event {
                     PlotAction::Cancel => *plot = Plot::Empty,
-                    _ => {
-                        unreachable!();
-                    }
+                    _ => continue,
                 },
                 Plot::Ready(crop, _) => match event {
                     PlotAction::Harvest(_) => {
@@ -532,17 +523,11 @@ impl Plugin {
                         *plot = Plot::Empty;
                     }
                     PlotAction::Compost(_) => {
-                        compost.0 += match crop {
-                            Crop::Carrot => CARROT_COMPOST,
-                            Crop::Clover => CLOVER_COMPOST,
-                            Crop::Wheat => WHEAT_COMPOST,
-                        };
+                        compost.earn(settings.compost_yield(crop));
                         sound_events.send(PlaySound("compost.ogg".to_owned()));
                         *plot = Plot::Empty;
                     }
-                    _ => {
-                        unreachable!();
-                    }
+                    _ => continue,
                 },
             }
         }
@@ -577,10 +562,10 @@ impl Plugin {
 
     fn update_plot(
         mut cmd: Commands,
-        time: Res<Time>,
+        (time, settings): (Res<SimTime>, Res<MatchSettings>),
         mut active_plot_circle: ResMut<ActivePlotCircle>,
         mut ev_plot: EventWriter<ActivatePlotCircle>,
-        mut compost: ResMut<Compost>,
+        (mut compost, mut stats): (ResMut<Compost>, ResMut<MatchStats>),
         q_plot_circle: Query<&PlotCircle>,
         mut q_plots: Query<(Entity, &mut Plot)>,
     ) {
@@ -589,11 +574,7 @@ impl Plugin {
             match &mut *plot {
                 Plot::Empty | Plot::Locked => {}
                 Plot::Growing(crop, ref mut t) => {
-                    match crop {
-                        Crop::Carrot => *t += delta / CARROT_GROW_TIME,
-                        Crop::Clover => *t += delta / CLOVER_GROW_TIME,
-                        Crop::Wheat => *t += delta / WHEAT_GROW_TIME,
-                    }
+                    *t += delta / settings.grow_time(crop);
                     if *t >= 1.0 {
                         if let Some(circle) = active_plot_circle.0 {
                             if let Ok(plot_circle) = q_plot_circle.get(circle) {
@@ -608,11 +589,7 @@ impl Plugin {
                     }
                 }
                 Plot::Ready(crop, ref mut t) => {
-                    match crop {
-                        Crop::Carrot => *t += delta / CARROT_DECAY_TIME,
-                        Crop::Clover => *t += delta / CLOVER_DECAY_TIME,
-                        Crop::Wheat => *t += delta / WHEAT_DECAY_TIME,
-                    }
+                    *t += delta / settings.decay_time(crop);
                     if *t >= 1.0 {
                         if let Some(circle) = active_plot_circle.0 {
                             if let Ok(plot_circle) = q_plot_circle.get(circle) {
@@ -623,11 +600,8 @@ impl Plugin {
                                 }
                             }
                         }
-                        compost.0 += match crop {
-                            Crop::Carrot => CARROT_COMPOST,
-                            Crop::Clover => CLOVER_COMPOST,
-                            Crop::Wheat => WHEAT_COMPOST,
-                        } / 2;
+                        compost.earn(settings.compost_yield(crop) / 2);
+                        *stats.decayed.entry(crop.clone()).or_default() += 1;
                         *plot = Plot::Empty;
                     }
                 }
@@ -639,29 +613,37 @@ impl Plugin {
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ActivePlotCircle>()
-            .init_resource::<Events<PlotAction>>()
+            .init_resource::<Events<PlotEvent>>()
             .init_resource::<Events<HarvestEvent>>()
             .add_event::<ActivatePlotCircle>()
             .add_enter_system(GameState::InGame, Self::init)
-            .add_system(
+            .add_system_to_stage(
+                FrameStage::Input,
                 Self::remove_plot_circle
                     .run_in_state(GameState::InGame)
+                    .run_in_state(MatchState::Running)
                     .before("plot_click"),
             )
-            .add_system(
+            .add_system_to_stage(
+                FrameStage::Input,
                 Self::plot_click
                     .run_in_state(GameState::InGame)
+                    .run_in_state(MatchState::Running)
                     .label("plot_click"),
             )
-            .add_system(
+            .add_system_to_stage(
+                FrameStage::Input,
                 Self::spawn_plot_circle
                     .run_in_state(GameState::InGame)
+                    .run_in_state(MatchState::Running)
                     .label("circle_spawn")
                     .after("plot_click"),
             )
-            .add_system(
+            .add_system_to_stage(
+                FrameStage::Input,
                 Self::plot_button_click
                     .run_in_state(GameState::InGame)
+                    .run_in_state(MatchState::Running)
                     .after("circle_spawn"),
             )
             .add_system(
@@ -669,8 +651,20 @@ impl bevy::app::Plugin for Plugin {
                     .run_in_state(GameState::InGame)
                     .after("circle_spawn"),
             )
-            .add_system(Self::handle_plot_event.run_in_state(GameState::InGame))
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_COMMANDS,
+                Self::handle_plot_event
+                    .run_in_state(GameState::InGame)
+                    .label("plot_event"),
+            )
             .add_system(Self::update_plot_overlay.run_in_state(GameState::InGame))
-            .add_system(Self::update_plot.run_in_state(GameState::InGame));
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_COMMANDS,
+                Self::update_plot
+                    .run_in_state(GameState::InGame)
+                    .after("plot_event"),
+            );
     }
 }
@@ -0,0 +1,429 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    challenge::ChallengeChoice,
+    consts::REPLAY_DIR,
+    difficulty::Difficulty,
+    faction::Faction,
+    level::LevelChoice,
+    match_settings::MatchSettings,
+    plot::{Plot, PlotAction, PlotEvent},
+    rng::{GameRng, SeedSetting},
+    scenario::ScenarioChoice,
+    sim::{SimTime, SIM, SIM_COMMANDS, SIM_LAST},
+    tutorial::Tutorial,
+    unit::{Unit, UnitCommand},
+    utils::{data_dir, unix_seconds},
+    GameState,
+};
+
+// Entity ids depend on everything the UI spawned, so replays refer to things by the order
+// the simulation created them in instead
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SimId(u32);
+
+#[derive(Resource, Default)]
+pub struct NextSimId(pub u32);
+
+impl NextSimId {
+    pub fn take(&mut self) -> SimId {
+        self.0 += 1;
+        SimId(self.0 - 1)
+    }
+}
+
+// Anything player actions can refer to
+type NeedsSimId = (Or<(With<Faction>, With<Plot>)>, Without<SimId>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ActionCommand {
+    Move([f32; 2]),
+    AttackMove([f32; 2]),
+    Attack(SimId),
+}
+
+// Everything the player can do that affects the simulation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PlayerAction {
+    Command {
+        units: Vec<SimId>,
+        command: ActionCommand,
+    },
+    Plot {
+        plot: SimId,
+        action: PlotAction,
+    },
+}
+
+// Actions from this frame's input, picked up by the next simulation tick
+#[derive(Resource, Default)]
+pub struct PendingActions(pub Vec<PlayerAction>);
+
+// Bump whenever a change to the simulation would make old replays play out differently
+const REPLAY_VERSION: u32 = 9;
+
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub level: LevelChoice,
+    pub scenario: ScenarioChoice,
+    pub challenge: ChallengeChoice,
+    pub settings: MatchSettings,
+    pub actions: Vec<(u64, PlayerAction)>,
+}
+
+impl Replay {
+    fn load(path: &PathBuf) -> Option<Replay> {
+        let bytes = std::fs::read(path)
+            .map_err(|err| warn!("Couldn't read replay {:?}: {}", path, err))
+            .ok()?;
+
+        Self::from_bytes(&bytes)
+            .map_err(|err| warn!("Couldn't load replay {:?}: {}", path, err))
+            .ok()
+    }
+
+    // The version comes first, so it can be checked before the rest, whose layout may differ
+    fn from_bytes(bytes: &[u8]) -> Result<Replay, String> {
+        let version = bincode::deserialize::<u32>(bytes).map_err(|err| err.to_string())?;
+        if version != REPLAY_VERSION {
+            return Err(format!("it's from an incompatible version ({})", version));
+        }
+
+        bincode::deserialize(bytes).map_err(|err| err.to_string())
+    }
+
+    fn save(&self) {
+        let dir = data_dir().join(REPLAY_DIR);
+        let path = dir.join(format!("{}.replay", unix_seconds()));
+
+        let result = bincode::serialize(self)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| {
+                std::fs::create_dir_all(&dir)
+                    .and_then(|_| std::fs::write(&path, bytes))
+                    .map_err(|err| err.to_string())
+            });
+
+        match result {
+            Ok(_) => info!("Saved replay to {:?}", path),
+            Err(err) => warn!("Couldn't save replay to {:?}: {}", path, err),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub enum ReplayMode {
+    Record(Replay),
+    Playback { replay: Replay, cursor: usize },
+    // Continued games don't start from their seed, so there's nothing to replay them from
+    Off,
+}
+
+impl ReplayMode {
+    pub fn is_playback(&self) -> bool {
+        matches!(self, ReplayMode::Playback { .. })
+    }
+
+    fn from_args() -> Option<Replay> {
+        let mut args = std::env::args();
+
+        while let Some(arg) = args.next() {
+            if arg == "--replay" {
+                return args
+                    .next()
+                    .and_then(|path| Replay::load(&PathBuf::from(path)));
+            }
+        }
+
+        None
+    }
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn reset(
+        mut time: ResMut<SimTime>,
+        mut next_id: ResMut<NextSimId>,
+        mut pending: ResMut<PendingActions>,
+    ) {
+        time.tick = 0;
+        next_id.0 = 0;
+        pending.0.clear();
+    }
+
+    fn start(
+        mut cmd: Commands,
+        rng: Res<GameRng>,
+        difficulty: Res<Difficulty>,
+        (level, scenario, challenge, settings): (
+            Res<LevelChoice>,
+            Res<ScenarioChoice>,
+            Res<ChallengeChoice>,
+            Res<MatchSettings>,
+        ),
+        mode: Option<Res<ReplayMode>>,
+        tutorial: Option<Res<Tutorial>>,
+    ) {
+        if mode.is_some_and(|mode| mode.is_playback()) {
+            return;
+        }
+
+        // The tutorial waits on box selections, which aren't recorded
+        if tutorial.is_some() {
+            cmd.insert_resource(ReplayMode::Off);
+            return;
+        }
+
+        cmd.insert_resource(ReplayMode::Record(Replay {
+            version: REPLAY_VERSION,
+            seed: rng.seed,
+            difficulty: *difficulty,
+            level: level.clone(),
+            scenario: scenario.clone(),
+            challenge: challenge.clone(),
+            settings: settings.clone(),
+            actions: Vec::new(),
+        }));
+    }
+
+    fn finish(mut cmd: Commands, mode: Res<ReplayMode>, mut seed: ResMut<SeedSetting>) {
+        match &*mode {
+            ReplayMode::Record(replay) => {
+                if !replay.actions.is_empty() {
+                    replay.save();
+                }
+            }
+            ReplayMode::Playback { .. } => seed.0 = None,
+            ReplayMode::Off => {}
+        }
+
+        cmd.remove_resource::<ReplayMode>();
+    }
+
+    // Sets up the game to start as soon as loading is done when launched with `--replay <file>`
+    fn start_playback(mut cmd: Commands, mut seed: ResMut<SeedSetting>) {
+        let Some(replay) = ReplayMode::from_args() else { return };
+
+        seed.0 = Some(replay.seed);
+        cmd.insert_resource(replay.difficulty);
+        cmd.insert_resource(replay.level.clone());
+        cmd.insert_resource(replay.scenario.clone());
+        cmd.insert_resource(replay.challenge.clone());
+        cmd.insert_resource(replay.settings.clone());
+
+        cmd.insert_resource(ReplayMode::Playback { replay, cursor: 0 });
+    }
+
+    fn assign_sim_ids(
+        mut cmd: Commands,
+        mut next_id: ResMut<NextSimId>,
+        q_new: Query<Entity, NeedsSimId>,
+    ) {
+        for entity in &q_new {
+            cmd.entity(entity).insert(next_id.take());
+        }
+    }
+
+    fn apply_actions(
+        time: Res<SimTime>,
+        mut mode: ResMut<ReplayMode>,
+        mut pending: ResMut<PendingActions>,
+        mut plot_events: EventWriter<PlotEvent>,
+        mut q_unit: Query<(&SimId, &mut Unit)>,
+        q_id: Query<(Entity, &SimId)>,
+    ) {
+        let actions = match &mut *mode {
+            ReplayMode::Record(replay) => {
+                let actions = std::mem::take(&mut pending.0);
+                replay
+                    .actions
+                    .extend(actions.iter().map(|action| (time.tick, action.clone())));
+                actions
+            }
+            ReplayMode::Playback { replay, cursor } => {
+                pending.0.clear();
+
+                let mut actions = Vec::new();
+                while let Some((tick, action)) = replay.actions.get(*cursor) {
+                    if *tick > time.tick {
+                        break;
+                    }
+                    actions.push(action.clone());
+                    *cursor += 1;
+                }
+                actions
+            }
+            ReplayMode::Off => std::mem::take(&mut pending.0),
+        };
+
+        let find = |id: SimId| {
+            q_id.iter()
+                .find(|(_, other)| **other == id)
+                .map(|(entity, _)| entity)
+        };
+
+        for action in actions {
+            match action {
+                PlayerAction::Command { units, command } => {
+                    let command = match command {
+                        ActionCommand::Move(pos) => UnitCommand::Move(pos.into()),
+                        ActionCommand::AttackMove(pos) => UnitCommand::AttackMove(pos.into()),
+                        ActionCommand::Attack(target) => {
+                            let Some(target) = find(target) else { continue };
+                            UnitCommand::Attack(target)
+                        }
+                    };
+
+                    for (id, mut unit) in &mut q_unit {
+                        if units.contains(id) {
+                            unit.set_command(command.clone());
+                        }
+                    }
+                }
+                PlayerAction::Plot { plot, action } => {
+                    let Some(plot) = find(plot) else { continue };
+                    plot_events.send(PlotEvent { plot, action });
+                }
+            }
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NextSimId>()
+            .init_resource::<PendingActions>()
+            .add_startup_system(Self::start_playback)
+            .add_enter_system(GameState::InGame, Self::reset)
+            .add_enter_system(GameState::InGame, Self::start)
+            .add_exit_system(GameState::InGame, Self::finish)
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_COMMANDS,
+                Self::apply_actions
+                    .run_in_state(GameState::InGame)
+                    .label("actions")
+                    .before("plot_event"),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_LAST,
+                Self::assign_sim_ids.run_in_state(GameState::InGame),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot::Crop;
+
+    fn empty_replay() -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            seed: default(),
+            difficulty: default(),
+            level: default(),
+            scenario: default(),
+            challenge: default(),
+            settings: default(),
+            actions: Vec::new(),
+        }
+    }
+
+    fn plant(crop: Crop) -> PlayerAction {
+        PlayerAction::Plot {
+            plot: SimId(0),
+            action: PlotAction::Plant(crop),
+        }
+    }
+
+    fn world(mode: ReplayMode) -> World {
+        let mut world = World::new();
+        world.init_resource::<SimTime>();
+        world.init_resource::<PendingActions>();
+        world.init_resource::<Events<PlotEvent>>();
+        world.insert_resource(mode);
+        world.spawn(SimId(0));
+        world
+    }
+
+    // Runs the given tick's actions, returning the crops they planted
+    fn tick(world: &mut World, tick: u64) -> Vec<Crop> {
+        world.resource_mut::<SimTime>().tick = tick;
+        SystemStage::single(Plugin::apply_actions).run(world);
+
+        world
+            .resource_mut::<Events<PlotEvent>>()
+            .drain()
+            .filter_map(|event| match event.action {
+                PlotAction::Plant(crop) => Some(crop),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recording_stamps_actions_with_the_tick_they_ran_on() {
+        let mut world = world(ReplayMode::Record(empty_replay()));
+
+        world
+            .resource_mut::<PendingActions>()
+            .0
+            .push(plant(Crop::Carrot));
+        assert_eq!(tick(&mut world, 4), [Crop::Carrot]);
+        assert!(tick(&mut world, 5).is_empty());
+        world
+            .resource_mut::<PendingActions>()
+            .0
+            .push(plant(Crop::Wheat));
+        assert_eq!(tick(&mut world, 7), [Crop::Wheat]);
+
+        let ReplayMode::Record(replay) = world.resource::<ReplayMode>() else { panic!("stopped recording") };
+        let ticks = replay
+            .actions
+            .iter()
+            .map(|(tick, _)| *tick)
+            .collect::<Vec<_>>();
+        assert_eq!(ticks, [4, 7]);
+    }
+
+    #[test]
+    fn playback_applies_actions_on_their_tick_and_ignores_input() {
+        let mut replay = empty_replay();
+        replay.actions = vec![
+            (3, plant(Crop::Carrot)),
+            (3, plant(Crop::Wheat)),
+            (5, plant(Crop::Clover)),
+        ];
+        let mut world = world(ReplayMode::Playback { replay, cursor: 0 });
+
+        world
+            .resource_mut::<PendingActions>()
+            .0
+            .push(plant(Crop::Clover));
+        assert!(tick(&mut world, 2).is_empty());
+        assert!(world.resource::<PendingActions>().0.is_empty());
+
+        assert_eq!(tick(&mut world, 3), [Crop::Carrot, Crop::Wheat]);
+        assert!(tick(&mut world, 4).is_empty());
+        assert_eq!(tick(&mut world, 5), [Crop::Clover]);
+    }
+
+    #[test]
+    fn older_replays_are_refused_for_their_version() {
+        // Stands in for an older layout that wouldn't parse as the current one anyway
+        let bytes = bincode::serialize(&(REPLAY_VERSION - 1, "garden.level.ron")).unwrap();
+
+        let err = Replay::from_bytes(&bytes).err();
+        assert!(err.is_some_and(|err| err.contains("incompatible version")));
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::{Mutex, MutexGuard};
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::GameState;
+
+// Every system that rolls dice gets its own stream, so the order systems happen to run in
+// doesn't change what each of them rolls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RngStream {
+    Waves,
+    Harvest,
+    Breeding,
+    EggLaying,
+    Wildlife,
+    Wander,
+}
+
+impl RngStream {
+    pub const ALL: [RngStream; 6] = [
+        RngStream::Waves,
+        RngStream::Harvest,
+        RngStream::Breeding,
+        RngStream::EggLaying,
+        RngStream::Wildlife,
+        RngStream::Wander,
+    ];
+}
+
+#[derive(Resource)]
+pub struct GameRng {
+    pub seed: u64,
+    streams: Vec<Mutex<fastrand::Rng>>,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        let master = fastrand::Rng::with_seed(seed);
+
+        GameRng {
+            seed,
+            streams: RngStream::ALL
+                .iter()
+                .map(|_| Mutex::new(fastrand::Rng::with_seed(master.u64(..))))
+                .collect(),
+        }
+    }
+
+    // Where each stream is up to, so a saved game carries on with the same rolls
+    pub fn state(&self) -> Vec<u64> {
+        RngStream::ALL
+            .iter()
+            .map(|stream| self.stream(*stream).get_seed())
+            .collect()
+    }
+
+    pub fn from_state(seed: u64, state: &[u64]) -> Self {
+        GameRng {
+            seed,
+            streams: state
+                .iter()
+                .map(|v| Mutex::new(fastrand::Rng::with_seed(*v)))
+                .collect(),
+        }
+    }
+
+    pub fn stream(&self, stream: RngStream) -> MutexGuard<'_, fastrand::Rng> {
+        self.streams[stream as usize].lock().unwrap()
+    }
+}
+
+// Seed for the next game, None picks a fresh one each time
+#[derive(Resource, Default)]
+pub struct SeedSetting(pub Option<u64>);
+
+impl SeedSetting {
+    fn from_args() -> Self {
+        let mut args = std::env::args();
+
+        while let Some(arg) = args.next() {
+            if arg == "--seed" {
+                return SeedSetting(args.next().and_then(|v| v.parse().ok()));
+            }
+        }
+
+        SeedSetting(None)
+    }
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn reseed(mut rng: ResMut<GameRng>, setting: Res<SeedSetting>) {
+        *rng = GameRng::new(setting.0.unwrap_or_else(|| fastrand::u64(..)));
+        info!("Game seed: {}", rng.seed);
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SeedSetting::from_args())
+            .insert_resource(GameRng::new(0))
+            .add_exit_system(GameState::MainMenu, Self::reseed)
+            // Covers replays, which start as soon as loading is done
+            .add_exit_system(GameState::Loading, Self::reseed)
+            // Covers restarting, which goes straight from one game into the next
+            .add_exit_system(GameState::InGame, Self::reseed);
+    }
+}
@@ -0,0 +1,634 @@
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::SystemTime;
+
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::app::AppExit;
+use bevy::{ecs::system::CommandQueue, prelude::*, utils::HashMap};
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::consts::SESSION_LOCK;
+use crate::{
+    challenge::ChallengeChoice,
+    consts::{AUTOSAVE_INTERVAL, AUTOSAVE_SLOTS, QUICKSAVE},
+    difficulty::Difficulty,
+    enemy::{
+        spawn_cocoon, spawn_egg, spawn_enemy, spawn_nest, spawn_nest_regrowth, Breeder, Cocoon,
+        Egg, EggLayer, EnemyKind, Larva, Nest, NestRegrowth,
+    },
+    faction::Faction,
+    game::{Compost, Rose},
+    health::{Health, HealthBar},
+    level::LevelChoice,
+    match_settings::MatchSettings,
+    plot::{Crop, Plot},
+    replay::{ActionCommand, NextSimId, ReplayMode, SimId},
+    rng::{GameRng, RngStream},
+    scenario::ScenarioChoice,
+    sim::{SimTime, SIM, SIM_PRE},
+    stats::MatchStats,
+    unit::{spawn_crop, Unit, UnitCommand},
+    utils::{save_dir, Bar},
+    waves::{WaveDirector, WaveModifiers},
+    wildlife::{spawn_wildlife, Wander, WildlifeKind, WildlifeSpawner},
+    GameState,
+};
+
+// Bump whenever the layout below changes, older saves are refused rather than misread
+const SAVE_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedPlot {
+    id: SimId,
+    pos: Vec2,
+    plot: Plot,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum SavedKind {
+    Crop(Crop),
+    Aphid(WaveModifiers, Breeder),
+    Caterpillar(WaveModifiers, Larva),
+    Butterfly(WaveModifiers, EggLayer),
+    // Cocoons have no unit left to scale, so their scaled max health is kept as is
+    Cocoon(f32, Cocoon),
+    Egg(Egg),
+    Nest(EnemyKind),
+    Wildlife(WildlifeKind, Wander),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedEntity {
+    id: SimId,
+    pos: Vec2,
+    // Kept as a fraction of max health, enemies get their wave scaling applied again on load
+    health: f32,
+    kind: SavedKind,
+    command: Option<ActionCommand>,
+}
+
+// Read on its own first, so an old save is refused for its version rather than for whichever
+// part of the layout changed since
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    version: u32,
+    pub difficulty: Difficulty,
+    #[serde(default)]
+    pub level: LevelChoice,
+    #[serde(default)]
+    pub scenario: ScenarioChoice,
+    #[serde(default)]
+    pub challenge: ChallengeChoice,
+    #[serde(default)]
+    pub settings: MatchSettings,
+    seed: u64,
+    rng: Vec<u64>,
+    tick: u64,
+    next_id: u32,
+    compost: u32,
+    #[serde(default)]
+    compost_earned: u32,
+    #[serde(default)]
+    compost_spent: u32,
+    #[serde(default)]
+    stats: MatchStats,
+    waves: WaveDirector,
+    wildlife: WildlifeSpawner,
+    rose: (SimId, f32),
+    plots: Vec<SavedPlot>,
+    entities: Vec<SavedEntity>,
+    regrowing: Vec<(Vec2, NestRegrowth)>,
+}
+
+impl SaveGame {
+    // Snapshot of the match in progress, None once the rose is gone
+    pub fn capture(world: &mut World) -> Option<SaveGame> {
+        let rose = world
+            .query_filtered::<(&SimId, &Health), With<Rose>>()
+            .get_single(world)
+            .map(|(id, health)| (*id, health.fraction()))
+            .ok()?;
+
+        let ids = world
+            .query::<(Entity, &SimId)>()
+            .iter(world)
+            .map(|(entity, id)| (entity, *id))
+            .collect::<HashMap<_, _>>();
+
+        let plots = world
+            .query::<(&SimId, &Plot, &Transform)>()
+            .iter(world)
+            .map(|(id, plot, transform)| SavedPlot {
+                id: *id,
+                pos: transform.translation.truncate(),
+                plot: plot.clone(),
+            })
+            .collect();
+
+        let mut entities = Vec::new();
+        let mut q_entity = world.query::<(Entity, &SimId, &Transform, &Health, Option<&Unit>)>();
+        for (entity, id, transform, health, unit) in q_entity.iter(world) {
+            let Some(kind) = Self::kind(world, entity) else { continue };
+
+            let command = unit
+                .and_then(|unit| unit.command())
+                .and_then(|command| match command {
+                    UnitCommand::Move(pos) => Some(ActionCommand::Move((*pos).into())),
+                    UnitCommand::AttackMove(pos) => Some(ActionCommand::AttackMove((*pos).into())),
+                    UnitCommand::Attack(target) => {
+                        ids.get(target).copied().map(ActionCommand::Attack)
+                    }
+                });
+
+            entities.push(SavedEntity {
+                id: *id,
+                pos: transform.translation.truncate(),
+                health: health.fraction(),
+                kind,
+                command,
+            });
+        }
+
+        let regrowing = world
+            .query::<(&NestRegrowth, &Transform)>()
+            .iter(world)
+            .map(|(regrowth, transform)| (transform.translation.truncate(), regrowth.clone()))
+            .collect();
+
+        let rng = world.resource::<GameRng>();
+
+        Some(SaveGame {
+            version: SAVE_VERSION,
+            difficulty: *world.resource::<Difficulty>(),
+            level: world.resource::<LevelChoice>().clone(),
+            scenario: world.resource::<ScenarioChoice>().clone(),
+            challenge: world.resource::<ChallengeChoice>().clone(),
+            settings: world.resource::<MatchSettings>().clone(),
+            seed: rng.seed,
+            rng: rng.state(),
+            tick: world.resource::<SimTime>().tick,
+            next_id: world.resource::<NextSimId>().0,
+            compost: world.resource::<Compost>().amount,
+            compost_earned: world.resource::<Compost>().earned,
+            compost_spent: world.resource::<Compost>().spent,
+            stats: world.resource::<MatchStats>().clone(),
+            waves: world.resource::<WaveDirector>().clone(),
+            wildlife: world.resource::<WildlifeSpawner>().clone(),
+            rose,
+            plots,
+            entities,
+            regrowing,
+        })
+    }
+
+    fn kind(world: &World, entity: Entity) -> Option<SavedKind> {
+        let modifiers = || {
+            world
+                .get::<WaveModifiers>(entity)
+                .copied()
+                .unwrap_or_default()
+        };
+
+        if let Some(crop) = world.get::<Crop>(entity) {
+            Some(SavedKind::Crop(crop.clone()))
+        } else if let Some(breeder) = world.get::<Breeder>(entity) {
+            Some(SavedKind::Aphid(modifiers(), breeder.clone()))
+        } else if let Some(larva) = world.get::<Larva>(entity) {
+            Some(SavedKind::Caterpillar(modifiers(), larva.clone()))
+        } else if let Some(layer) = world.get::<EggLayer>(entity) {
+            Some(SavedKind::Butterfly(modifiers(), layer.clone()))
+        } else if let Some(cocoon) = world.get::<Cocoon>(entity) {
+            let health = world.get::<Health>(entity)?;
+            Some(SavedKind::Cocoon(health.max(), cocoon.clone()))
+        } else if let Some(egg) = world.get::<Egg>(entity) {
+            Some(SavedKind::Egg(egg.clone()))
+        } else if let Some(nest) = world.get::<Nest>(entity) {
+            Some(SavedKind::Nest(nest.kind))
+        } else if let Some(kind) = world.get::<WildlifeKind>(entity) {
+            let wander = world.get::<Wander>(entity)?;
+            Some(SavedKind::Wildlife(*kind, wander.clone()))
+        } else {
+            None
+        }
+    }
+
+    pub fn path(name: &str) -> PathBuf {
+        save_dir().join(name)
+    }
+
+    pub fn exists(name: &str) -> bool {
+        Self::path(name).exists()
+    }
+
+    pub fn write(&self, name: &str) {
+        let path = Self::path(name);
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                std::fs::create_dir_all(save_dir())
+                    .and_then(|_| std::fs::write(&path, text))
+                    .map_err(|err| err.to_string())
+            });
+
+        match result {
+            Ok(_) => info!("Saved game to {:?}", path),
+            Err(err) => warn!("Couldn't save game to {:?}: {}", path, err),
+        }
+    }
+
+    // The most recently written autosave slot, if there are any
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn latest_autosave() -> Option<String> {
+        (0..AUTOSAVE_SLOTS)
+            .map(Self::autosave_name)
+            .filter_map(|name| {
+                let modified = Self::path(&name)
+                    .metadata()
+                    .and_then(|v| v.modified())
+                    .ok()?;
+                Some((name, modified))
+            })
+            .max_by_key(|(_, modified)| *modified)
+            .map(|(name, _)| name)
+    }
+
+    fn autosave_name(slot: usize) -> String {
+        format!("autosave_{}.ron", slot)
+    }
+
+    pub fn load(name: &str) -> Option<SaveGame> {
+        let path = Self::path(name);
+        let text = std::fs::read_to_string(&path)
+            .map_err(|err| warn!("Couldn't read save {:?}: {}", path, err))
+            .ok()?;
+
+        Self::parse(&text)
+            .map_err(|err| warn!("Couldn't load save {:?}: {}", path, err))
+            .ok()
+    }
+
+    fn parse(text: &str) -> Result<SaveGame, String> {
+        let header = ron::from_str::<SaveHeader>(text).map_err(|err| err.to_string())?;
+        if header.version != SAVE_VERSION {
+            return Err(format!(
+                "it's from an incompatible version ({})",
+                header.version
+            ));
+        }
+
+        let save = ron::from_str::<SaveGame>(text).map_err(|err| err.to_string())?;
+        if save.rng.len() != RngStream::ALL.len() {
+            return Err(format!(
+                "it has {} random streams instead of {}",
+                save.rng.len(),
+                RngStream::ALL.len()
+            ));
+        }
+
+        Ok(save)
+    }
+}
+
+// A save waiting for the game it's continuing to start
+#[derive(Resource)]
+pub struct LoadedGame(pub SaveGame);
+
+// Autosaves go round the slots, so a save that gets cut off by a crash only costs one of them
+#[derive(Resource, Default)]
+struct Autosave {
+    slot: usize,
+    // Number of autosave intervals the current game had been through at the last autosave
+    done: Option<u64>,
+}
+
+// Set when the last session never got to remove its lock file, holds the autosave to offer
+#[derive(Resource)]
+pub struct RecoveryPrompt(pub String);
+
+pub struct Plugin;
+
+impl Plugin {
+    fn quick_save(world: &mut World) {
+        if world.resource::<CurrentState<GameState>>().0 != GameState::InGame
+            || !world.resource::<Input<KeyCode>>().just_pressed(KeyCode::F5)
+        {
+            return;
+        }
+
+        match SaveGame::capture(world) {
+            Some(save) => save.write(QUICKSAVE),
+            None => info!("Nothing to save, the game is over"),
+        }
+    }
+
+    fn autosave(world: &mut World) {
+        if world.resource::<CurrentState<GameState>>().0 != GameState::InGame {
+            return;
+        }
+
+        let due = world.resource::<WaveDirector>().total.as_secs() / AUTOSAVE_INTERVAL;
+        let autosave = world.resource::<Autosave>();
+
+        // The first check of a game only catches up, continued games don't save straight away
+        let Some(done) = autosave.done else {
+            world.resource_mut::<Autosave>().done = Some(due);
+            return;
+        };
+        if due <= done {
+            return;
+        }
+
+        let slot = autosave.slot;
+        if let Some(save) = SaveGame::capture(world) {
+            save.write(&SaveGame::autosave_name(slot));
+        }
+
+        let mut autosave = world.resource_mut::<Autosave>();
+        autosave.done = Some(due);
+        autosave.slot = (slot + 1) % AUTOSAVE_SLOTS;
+    }
+
+    fn reset_autosave(mut autosave: ResMut<Autosave>) {
+        autosave.done = None;
+    }
+
+    // The lock file lives as long as the game is running, finding one at startup means the last
+    // session crashed instead of exiting
+    #[cfg(not(target_arch = "wasm32"))]
+    fn lock_session(mut cmd: Commands) {
+        let lock = SaveGame::path(SESSION_LOCK);
+
+        if lock.exists() {
+            warn!("The last session didn't exit cleanly");
+            if let Some(name) = SaveGame::latest_autosave() {
+                cmd.insert_resource(RecoveryPrompt(name));
+            }
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |v| v.as_secs());
+        let result = std::fs::create_dir_all(save_dir())
+            .and_then(|_| std::fs::write(&lock, timestamp.to_string()));
+
+        if let Err(err) = result {
+            warn!("Couldn't create session lock {:?}: {}", lock, err);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn unlock_session(mut exit: EventReader<AppExit>) {
+        if exit.iter().next().is_some() {
+            let _ = std::fs::remove_file(SaveGame::path(SESSION_LOCK));
+        }
+    }
+
+    // Runs on the first tick of a continued game, swapping the fresh garden for the saved one
+    fn restore(world: &mut World) {
+        let Some(LoadedGame(save)) = world.remove_resource::<LoadedGame>() else { return };
+
+        world.insert_resource(GameRng::from_state(save.seed, &save.rng));
+        world.insert_resource(ReplayMode::Off);
+        world.insert_resource(Compost {
+            amount: save.compost,
+            earned: save.compost_earned,
+            spent: save.compost_spent,
+        });
+        world.insert_resource(save.stats);
+        world.insert_resource(save.wildlife);
+        world.resource_mut::<SimTime>().tick = save.tick;
+        world.resource_mut::<NextSimId>().0 = save.next_id;
+        world.resource_mut::<WaveDirector>().restore(save.waves);
+
+        let mut entities = HashMap::new();
+
+        let mut q_rose = world.query_filtered::<(Entity, &mut Health), With<Rose>>();
+        if let Ok((rose, mut health)) = q_rose.get_single_mut(world) {
+            let (id, fraction) = save.rose;
+            health.set_fraction(fraction);
+            world.entity_mut(rose).insert(id);
+            entities.insert(id, rose);
+        }
+
+        let mut q_plot = world.query::<(Entity, &mut Plot, &Transform)>();
+        for saved in save.plots {
+            let nearest = q_plot.iter_mut(world).min_by(|a, b| {
+                let a = a.2.translation.truncate().distance(saved.pos);
+                let b = b.2.translation.truncate().distance(saved.pos);
+                a.total_cmp(&b)
+            });
+            let Some((entity, mut plot, _)) = nearest else { continue };
+
+            *plot = saved.plot;
+            world.entity_mut(entity).insert(saved.id);
+            entities.insert(saved.id, entity);
+        }
+
+        let mut q_fresh = world.query_filtered::<Entity, (With<Faction>, Without<Rose>)>();
+        for entity in q_fresh.iter(world).collect::<Vec<_>>() {
+            world.entity_mut(entity).despawn_recursive();
+        }
+
+        let assets = world.resource::<AssetServer>().clone();
+        let mut queue = CommandQueue::default();
+        let mut cmd = Commands::new(&mut queue, world);
+
+        let mut spawned = Vec::new();
+        for saved in save.entities {
+            let max_health = match saved.kind {
+                SavedKind::Cocoon(max_health, _) => Some(max_health),
+                _ => None,
+            };
+
+            let entity = match saved.kind {
+                SavedKind::Crop(crop) => spawn_crop(&mut cmd, &assets, crop, saved.pos),
+                SavedKind::Aphid(modifiers, breeder) => {
+                    let entity = spawn_enemy(&mut cmd, &assets, EnemyKind::Aphid, saved.pos);
+                    cmd.entity(entity).insert((modifiers, breeder));
+                    entity
+                }
+                SavedKind::Caterpillar(modifiers, larva) => {
+                    let entity = spawn_enemy(&mut cmd, &assets, EnemyKind::Caterpillar, saved.pos);
+                    cmd.entity(entity).insert((modifiers, larva));
+                    entity
+                }
+                SavedKind::Butterfly(modifiers, layer) => {
+                    let entity = spawn_enemy(&mut cmd, &assets, EnemyKind::Butterfly, saved.pos);
+                    cmd.entity(entity).insert((modifiers, layer));
+                    entity
+                }
+                SavedKind::Cocoon(_, cocoon) => {
+                    let entity = spawn_cocoon(&mut cmd, &assets, saved.pos);
+                    cmd.entity(entity).insert(cocoon);
+                    entity
+                }
+                SavedKind::Egg(egg) => {
+                    let entity = spawn_egg(&mut cmd, &assets, saved.pos);
+                    cmd.entity(entity).insert(egg);
+                    entity
+                }
+                SavedKind::Nest(kind) => spawn_nest(&mut cmd, &assets, kind, saved.pos),
+                SavedKind::Wildlife(kind, wander) => {
+                    let entity = spawn_wildlife(&mut cmd, &assets, kind, saved.pos);
+                    cmd.entity(entity).insert(wander);
+                    entity
+                }
+            };
+
+            cmd.entity(entity).insert(saved.id);
+            entities.insert(saved.id, entity);
+            spawned.push((entity, max_health, saved.health, saved.command));
+        }
+
+        for (pos, regrowth) in save.regrowing {
+            spawn_nest_regrowth(&mut cmd, &assets, regrowth, pos);
+        }
+
+        queue.apply(world);
+
+        let mut q_healthbar = world.query_filtered::<&mut Bar, With<HealthBar>>();
+        for (entity, max_health, health, command) in spawned {
+            if let Some(max_health) = max_health {
+                world.entity_mut(entity).insert(Health::new(max_health));
+
+                let children = world
+                    .get::<Children>(entity)
+                    .map(|children| children.to_vec());
+                for child in children.unwrap_or_default() {
+                    if let Ok(mut bar) = q_healthbar.get_mut(world, child) {
+                        bar.max = max_health;
+                    }
+                }
+            }
+
+            if let Some(mut current) = world.get_mut::<Health>(entity) {
+                current.set_fraction(health);
+            }
+
+            let command = command.and_then(|command| match command {
+                ActionCommand::Move(pos) => Some(UnitCommand::Move(pos.into())),
+                ActionCommand::AttackMove(pos) => Some(UnitCommand::AttackMove(pos.into())),
+                ActionCommand::Attack(target) => {
+                    entities.get(&target).copied().map(UnitCommand::Attack)
+                }
+            });
+
+            if let (Some(command), Some(mut unit)) = (command, world.get_mut::<Unit>(entity)) {
+                unit.set_command(command);
+            }
+        }
+
+        info!("Continued saved game at tick {}", save.tick);
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Autosave>()
+            .add_enter_system(GameState::InGame, Self::reset_autosave)
+            .add_system(Self::quick_save)
+            .add_system(Self::autosave)
+            .add_fixed_timestep_system(SIM, SIM_PRE, Self::restore);
+
+        // The web build has no files to keep a lock in, and no crashes to come back from
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_startup_system(Self::lock_session)
+            .add_system_to_stage(CoreStage::Last, Self::unlock_session);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn older_saves_are_refused_for_their_version() {
+        // Stands in for an older layout that wouldn't parse as the current one anyway
+        let text = format!("(version: {}, wave_timer: 3.0)", SAVE_VERSION - 1);
+
+        let err = SaveGame::parse(&text).err();
+        assert!(err.is_some_and(|err| err.contains("incompatible version")));
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .insert_resource(GameRng::new(1))
+            .insert_resource(Difficulty::Hard)
+            .insert_resource(Compost::new(0))
+            .insert_resource(WaveDirector::new(default()))
+            .init_resource::<LevelChoice>()
+            .init_resource::<ScenarioChoice>()
+            .init_resource::<ChallengeChoice>()
+            .init_resource::<MatchSettings>()
+            .init_resource::<SimTime>()
+            .init_resource::<NextSimId>()
+            .init_resource::<MatchStats>()
+            .init_resource::<WildlifeSpawner>();
+
+        let id = app.world.resource_mut::<NextSimId>().take();
+        app.world
+            .spawn((Rose, Health::new(100.0), Transform::default(), id));
+        app
+    }
+
+    #[test]
+    fn saves_with_missing_random_streams_are_refused() {
+        let mut app = app();
+        let mut save = SaveGame::capture(&mut app.world).unwrap();
+        save.rng.pop();
+        let text = ron::to_string(&save).unwrap();
+
+        let err = SaveGame::parse(&text).err();
+        assert!(err.is_some_and(|err| err.contains("random streams")));
+    }
+
+    #[test]
+    fn continued_games_keep_orders_and_cocoon_health() {
+        let mut app = app();
+        let world = &mut app.world;
+        let assets = world.resource::<AssetServer>().clone();
+        let mut queue = CommandQueue::default();
+        let mut cmd = Commands::new(&mut queue, world);
+        let crop = spawn_crop(&mut cmd, &assets, Crop::Carrot, Vec2::new(10.0, 0.0));
+        let caterpillar = spawn_enemy(&mut cmd, &assets, EnemyKind::Caterpillar, Vec2::ZERO);
+        let cocoon = spawn_cocoon(&mut cmd, &assets, Vec2::new(0.0, 10.0));
+        queue.apply(world);
+
+        for entity in [crop, caterpillar, cocoon] {
+            let id = world.resource_mut::<NextSimId>().take();
+            world.entity_mut(entity).insert(id);
+        }
+
+        let mut unit = world.get_mut::<Unit>(caterpillar).unwrap();
+        unit.set_command(UnitCommand::Attack(crop));
+        // As if the wave scaling had doubled it before the caterpillar cocooned
+        let mut health = world.get_mut::<Health>(cocoon).unwrap();
+        health.scale(2.0);
+        health.set_fraction(0.5);
+        let max_health = health.max();
+
+        let save = SaveGame::capture(world).unwrap();
+        world.insert_resource(LoadedGame(save));
+        Plugin::restore(world);
+
+        let mut q_larva = world.query_filtered::<&Unit, With<Larva>>();
+        let command = q_larva.single(world).command().cloned();
+        let mut q_crop = world.query_filtered::<Entity, With<Crop>>();
+        let crop = q_crop.single(world);
+        assert!(matches!(command, Some(UnitCommand::Attack(target)) if target == crop));
+
+        let mut q_cocoon = world.query_filtered::<&Health, With<Cocoon>>();
+        let health = q_cocoon.single(world);
+        assert_eq!(health.max(), max_health);
+        assert_eq!(health.fraction(), 0.5);
+    }
+}
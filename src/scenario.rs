@@ -0,0 +1,441 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{CAMPAIGN, CAMPAIGN_FILE},
+    difficulty::Difficulty,
+    level::LevelChoice,
+    pause::MatchState,
+    plot::Crop,
+    replay::ReplayMode,
+    stats::MatchStats,
+    utils::save_dir,
+    waves::WaveDirector,
+    GameState,
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Objective {
+    Survive { seconds: u64 },
+    Harvest { crop: Crop, amount: u32 },
+    DestroyNests { amount: u32 },
+    // Lost as soon as a crop is left to rot on its plot, won once the deadline passes without one
+    KeepPlotsAlive { until: Deadline },
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Deadline {
+    Seconds(u64),
+    Wave(u32),
+}
+
+impl Deadline {
+    fn reached(&self, director: &WaveDirector) -> bool {
+        match self {
+            Deadline::Seconds(seconds) => director.total.as_secs() >= *seconds,
+            Deadline::Wave(wave) => director.wave >= *wave,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pending,
+    Done,
+    Failed,
+}
+
+impl Objective {
+    pub fn status(&self, stats: &MatchStats, director: &WaveDirector) -> Status {
+        let done = match self {
+            Objective::Survive { seconds } => director.total.as_secs() >= *seconds,
+            Objective::Harvest { crop, amount } => {
+                stats.harvested.get(crop).unwrap_or(&0) >= amount
+            }
+            Objective::DestroyNests { amount } => stats.nests_destroyed >= *amount,
+            Objective::KeepPlotsAlive { until } => {
+                if !stats.decayed.is_empty() {
+                    return Status::Failed;
+                }
+                until.reached(director)
+            }
+        };
+
+        if done {
+            Status::Done
+        } else {
+            Status::Pending
+        }
+    }
+
+    pub fn describe(&self, stats: &MatchStats, director: &WaveDirector) -> String {
+        match self {
+            Objective::Survive { seconds } => format!(
+                "Survive {:02}:{:02} ({:02}:{:02})",
+                seconds / 60,
+                seconds % 60,
+                director.total.as_secs().min(*seconds) / 60,
+                director.total.as_secs().min(*seconds) % 60
+            ),
+            Objective::Harvest { crop, amount } => format!(
+                "Harvest {} {} ({})",
+                amount,
+                crop.name(),
+                stats.harvested.get(crop).unwrap_or(&0).min(amount)
+            ),
+            Objective::DestroyNests { amount } => format!(
+                "Destroy {} nests ({})",
+                amount,
+                stats.nests_destroyed.min(*amount)
+            ),
+            Objective::KeepPlotsAlive { until } => match until {
+                Deadline::Seconds(seconds) => format!(
+                    "Don't let a crop rot for {:02}:{:02}",
+                    seconds / 60,
+                    seconds % 60
+                ),
+                Deadline::Wave(wave) => format!("Don't let a crop rot before wave {}", wave),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    pub briefing: String,
+    // File name of the map it's played on
+    pub level: String,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    pub objectives: Vec<Objective>,
+}
+
+impl Scenario {
+    // Won once every objective is done, lost as soon as one fails
+    pub fn status(&self, stats: &MatchStats, director: &WaveDirector) -> Status {
+        let statuses = self
+            .objectives
+            .iter()
+            .map(|objective| objective.status(stats, director))
+            .collect::<Vec<_>>();
+
+        if statuses.contains(&Status::Failed) {
+            Status::Failed
+        } else if statuses.iter().all(|status| status == &Status::Done) {
+            Status::Done
+        } else {
+            Status::Pending
+        }
+    }
+}
+
+// The scenarios in the order they unlock, read from `assets/garden.campaign.ron`
+#[derive(Resource, Default, Clone, TypeUuid)]
+#[uuid = "b617af12-9476-48af-aaed-f49b3e283435"]
+pub struct Campaign(pub Vec<Scenario>);
+
+#[derive(Default)]
+pub struct CampaignLoader;
+
+impl AssetLoader for CampaignLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let scenarios = ron::de::from_bytes::<Vec<Scenario>>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(Campaign(scenarios)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["campaign.ron"]
+    }
+}
+
+// The campaign file while it's loading, Campaign is inserted once it's done
+#[derive(Resource)]
+struct CampaignHandle(Handle<Campaign>);
+
+// How far into the campaign the player has got, kept next to the saves
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CampaignProgress {
+    pub completed: usize,
+}
+
+impl CampaignProgress {
+    fn load() -> Self {
+        let path = save_dir().join(CAMPAIGN_FILE);
+        let Ok(text) = std::fs::read_to_string(&path) else { return CampaignProgress::default() };
+
+        ron::from_str(&text).unwrap_or_else(|err| {
+            warn!("Couldn't parse campaign progress {:?}: {}", path, err);
+            CampaignProgress::default()
+        })
+    }
+
+    fn write(&self) {
+        let path = save_dir().join(CAMPAIGN_FILE);
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                std::fs::create_dir_all(save_dir())
+                    .and_then(|_| std::fs::write(&path, text))
+                    .map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            warn!("Couldn't save campaign progress to {:?}: {}", path, err);
+        }
+    }
+
+    pub fn is_unlocked(&self, index: usize) -> bool {
+        index <= self.completed
+    }
+}
+
+// Index into the campaign of the scenario being played, None for endless survival
+#[derive(Resource, Default, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScenarioChoice(pub Option<usize>);
+
+impl ScenarioChoice {
+    pub fn get<'a>(&self, campaign: &'a Campaign) -> Option<&'a Scenario> {
+        campaign.0.get(self.0?)
+    }
+}
+
+// Sets up a game of the given scenario, ready for GameState::InGame
+pub fn start_scenario(cmd: &mut Commands, campaign: &Campaign, index: usize) {
+    let Some(scenario) = campaign.0.get(index) else { return };
+
+    cmd.insert_resource(ScenarioChoice(Some(index)));
+    cmd.insert_resource(LevelChoice(scenario.level.clone()));
+    cmd.insert_resource(scenario.difficulty);
+    cmd.insert_resource(NextState(GameState::InGame));
+}
+
+pub fn spawn_objectives(
+    parent: &mut ChildBuilder,
+    assets: &AssetServer,
+    scenario: &Scenario,
+    stats: &MatchStats,
+    director: &WaveDirector,
+) {
+    for objective in &scenario.objectives {
+        let color = match objective.status(stats, director) {
+            Status::Pending => Color::WHITE,
+            Status::Done => Color::GREEN,
+            Status::Failed => Color::RED,
+        };
+
+        parent.spawn(TextBundle::from_section(
+            objective.describe(stats, director),
+            TextStyle {
+                font: assets.load("fonts/ModeSeven.ttf"),
+                font_size: 20.0,
+                color,
+            },
+        ));
+    }
+}
+
+#[derive(Component)]
+pub struct ObjectiveText;
+
+#[derive(Component)]
+pub struct NextScenarioButton;
+
+pub struct Plugin;
+
+impl Plugin {
+    fn load(mut cmd: Commands, assets: Res<AssetServer>) {
+        cmd.insert_resource(CampaignHandle(assets.load(CAMPAIGN)));
+    }
+
+    fn collect(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        handle: Res<CampaignHandle>,
+        campaigns: Res<Assets<Campaign>>,
+    ) {
+        if assets.get_load_state(&handle.0) == LoadState::Loading {
+            return;
+        }
+
+        let campaign = campaigns.get(&handle.0).cloned().unwrap_or_else(|| {
+            warn!("Couldn't load the campaign {:?}", CAMPAIGN);
+            Campaign::default()
+        });
+
+        cmd.remove_resource::<CampaignHandle>();
+        cmd.insert_resource(campaign);
+    }
+
+    fn init(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        campaign: Res<Campaign>,
+        choice: Res<ScenarioChoice>,
+    ) {
+        let Some(scenario) = choice.get(&campaign) else { return };
+
+        cmd.spawn((
+            TextBundle::from_sections([
+                TextSection::new(
+                    format!("{}\n", scenario.name),
+                    TextStyle {
+                        font: assets.load("fonts/ModeSeven.ttf"),
+                        font_size: 20.0,
+                        color: Color::YELLOW,
+                    },
+                ),
+                TextSection::new(
+                    "",
+                    TextStyle {
+                        font: assets.load("fonts/ModeSeven.ttf"),
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                ),
+            ])
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+            ObjectiveText,
+        ));
+    }
+
+    fn update_text(
+        campaign: Res<Campaign>,
+        choice: Res<ScenarioChoice>,
+        stats: Res<MatchStats>,
+        director: Res<WaveDirector>,
+        mut q_text: Query<&mut Text, With<ObjectiveText>>,
+    ) {
+        let Some(scenario) = choice.get(&campaign) else { return };
+        let Ok(mut text) = q_text.get_single_mut() else { return };
+
+        text.sections[1].value = scenario
+            .objectives
+            .iter()
+            .map(|objective| {
+                let marker = match objective.status(&stats, &director) {
+                    Status::Pending => " ",
+                    Status::Done => "+",
+                    Status::Failed => "x",
+                };
+                format!("{} {}", marker, objective.describe(&stats, &director))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    fn check_objectives(
+        mut cmd: Commands,
+        campaign: Res<Campaign>,
+        choice: Res<ScenarioChoice>,
+        stats: Res<MatchStats>,
+        director: Res<WaveDirector>,
+    ) {
+        let Some(scenario) = choice.get(&campaign) else { return };
+
+        match scenario.status(&stats, &director) {
+            Status::Done => cmd.insert_resource(NextState(MatchState::Victory)),
+            Status::Failed => cmd.insert_resource(NextState(MatchState::GameOver)),
+            Status::Pending => {}
+        }
+    }
+
+    fn unlock_next(
+        mut progress: ResMut<CampaignProgress>,
+        choice: Res<ScenarioChoice>,
+        mode: Option<Res<ReplayMode>>,
+    ) {
+        if mode.is_some_and(|mode| mode.is_playback()) {
+            return;
+        }
+        let Some(index) = choice.0 else { return };
+
+        if index + 1 > progress.completed {
+            progress.completed = index + 1;
+            progress.write();
+        }
+    }
+
+    fn handle_next_click(
+        mut cmd: Commands,
+        campaign: Res<Campaign>,
+        choice: Res<ScenarioChoice>,
+        q_button: Query<&Interaction, (Changed<Interaction>, With<NextScenarioButton>)>,
+        mouse: Res<Input<MouseButton>>,
+    ) {
+        if mouse.just_released(MouseButton::Left) {
+            for button in &q_button {
+                if button == &Interaction::Hovered {
+                    let Some(index) = choice.0 else { continue };
+                    start_scenario(&mut cmd, &campaign, index + 1);
+                }
+            }
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Campaign>()
+            .init_asset_loader::<CampaignLoader>()
+            .insert_resource(CampaignProgress::load())
+            .add_startup_system(Self::load)
+            .add_system(Self::collect.run_if_resource_exists::<CampaignHandle>())
+            .init_resource::<ScenarioChoice>()
+            .add_enter_system(GameState::InGame, Self::init)
+            .add_enter_system(MatchState::Victory, Self::unlock_next)
+            .add_system(Self::update_text.run_in_state(GameState::InGame))
+            .add_system(Self::handle_next_click.run_in_state(GameState::InGame))
+            .add_system(
+                Self::check_objectives
+                    .run_in_state(GameState::InGame)
+                    .run_in_state(MatchState::Running),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plots_are_kept_alive_until_the_deadline() {
+        let objective = Objective::KeepPlotsAlive {
+            until: Deadline::Wave(3),
+        };
+        let mut stats = MatchStats::default();
+        let mut director = WaveDirector::new(default());
+
+        director.wave = 2;
+        assert!(objective.status(&stats, &director) == Status::Pending);
+
+        director.wave = 3;
+        assert!(objective.status(&stats, &director) == Status::Done);
+
+        director.wave = 1;
+        stats.decayed.insert(Crop::Carrot, 1);
+        assert!(objective.status(&stats, &director) == Status::Failed);
+    }
+}
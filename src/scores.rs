@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    challenge::ChallengeChoice,
+    consts::{MAX_HIGH_SCORES, SCORES_FILE},
+    difficulty::Difficulty,
+    game::Compost,
+    match_settings::MatchSettings,
+    pause::MatchState,
+    replay::ReplayMode,
+    rng::GameRng,
+    scenario::ScenarioChoice,
+    stats::MatchStats,
+    utils::save_dir,
+    waves::WaveDirector,
+};
+
+// Bump whenever a change to the scoring would make old entries unfair to compare against
+const SCORES_VERSION: u32 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Score {
+    pub seconds: u64,
+    pub wave: u32,
+    pub kills: u32,
+    pub compost: u32,
+    pub difficulty: Difficulty,
+    pub seed: u64,
+}
+
+impl Score {
+    fn row(&self, rank: usize) -> String {
+        format!(
+            "{:>2}. {:02}:{:02}  wave {:>2}  kills {:>4}  compost {:>5}  {:<9}  #{}",
+            rank + 1,
+            self.seconds / 60,
+            self.seconds % 60,
+            self.wave,
+            self.kills,
+            self.compost,
+            self.difficulty.name(),
+            self.seed
+        )
+    }
+}
+
+// Longest survival first, kept next to the saves
+#[derive(Resource, Serialize, Deserialize)]
+pub struct HighScores {
+    version: u32,
+    pub entries: Vec<Score>,
+    // Rank of the run that just ended, if it made the table
+    #[serde(skip)]
+    pub latest: Option<usize>,
+}
+
+impl Default for HighScores {
+    fn default() -> Self {
+        HighScores {
+            version: SCORES_VERSION,
+            entries: Vec::new(),
+            latest: None,
+        }
+    }
+}
+
+impl HighScores {
+    fn load() -> Self {
+        let path = save_dir().join(SCORES_FILE);
+        let Ok(text) = std::fs::read_to_string(&path) else { return HighScores::default() };
+
+        match ron::from_str::<HighScores>(&text) {
+            Ok(scores) if scores.version == SCORES_VERSION => scores,
+            Ok(_) => {
+                warn!("High scores {:?} are from an incompatible version", path);
+                HighScores::default()
+            }
+            Err(err) => {
+                warn!("Couldn't parse high scores {:?}: {}", path, err);
+                HighScores::default()
+            }
+        }
+    }
+
+    fn write(&self) {
+        let path = save_dir().join(SCORES_FILE);
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                std::fs::create_dir_all(save_dir())
+                    .and_then(|_| std::fs::write(&path, text))
+                    .map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            warn!("Couldn't save high scores to {:?}: {}", path, err);
+        }
+    }
+
+    fn insert(&mut self, score: Score) -> Option<usize> {
+        let rank = self
+            .entries
+            .iter()
+            .position(|entry| entry.seconds < score.seconds)
+            .unwrap_or(self.entries.len());
+
+        if rank >= MAX_HIGH_SCORES {
+            return None;
+        }
+
+        self.entries.insert(rank, score);
+        self.entries.truncate(MAX_HIGH_SCORES);
+        Some(rank)
+    }
+}
+
+pub fn spawn_score_table(parent: &mut ChildBuilder, assets: &AssetServer, scores: &HighScores) {
+    if scores.entries.is_empty() {
+        parent.spawn(TextBundle::from_section(
+            "No runs yet",
+            TextStyle {
+                font: assets.load("fonts/ModeSeven.ttf"),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        ));
+        return;
+    }
+
+    for (rank, score) in scores.entries.iter().enumerate() {
+        let color = if scores.latest == Some(rank) {
+            Color::YELLOW
+        } else {
+            Color::WHITE
+        };
+
+        parent.spawn(TextBundle::from_section(
+            score.row(rank),
+            TextStyle {
+                font: assets.load("fonts/ModeSeven.ttf"),
+                font_size: 20.0,
+                color,
+            },
+        ));
+    }
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn record(
+        mut scores: ResMut<HighScores>,
+        director: Res<WaveDirector>,
+        compost: Res<Compost>,
+        stats: Res<MatchStats>,
+        difficulty: Res<Difficulty>,
+        (rng, scenario, challenge, settings): (
+            Res<GameRng>,
+            Res<ScenarioChoice>,
+            Res<ChallengeChoice>,
+            Res<MatchSettings>,
+        ),
+        mode: Option<Res<ReplayMode>>,
+    ) {
+        // Watching a replay isn't a run of its own, scenarios aren't endless survival,
+        // challenges keep their own bests and custom games aren't comparable
+        if mode.is_some_and(|mode| mode.is_playback())
+            || scenario.0.is_some()
+            || challenge.0.is_some()
+            || !settings.is_default()
+        {
+            scores.latest = None;
+            return;
+        }
+
+        scores.latest = scores.insert(Score {
+            seconds: director.total.as_secs(),
+            wave: director.wave,
+            kills: stats.total_kills(),
+            compost: compost.earned,
+            difficulty: *difficulty,
+            seed: rng.seed,
+        });
+
+        if scores.latest.is_some() {
+            scores.write();
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HighScores::load())
+            .add_enter_system(MatchState::GameOver, Self::record.label("record_score"))
+            .add_enter_system(MatchState::Victory, Self::record.label("record_score"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(seconds: u64, seed: u64) -> Score {
+        Score {
+            seconds,
+            wave: 1,
+            kills: 0,
+            compost: 0,
+            difficulty: Difficulty::Normal,
+            seed,
+        }
+    }
+
+    fn seeds(scores: &HighScores) -> Vec<u64> {
+        scores.entries.iter().map(|entry| entry.seed).collect()
+    }
+
+    #[test]
+    fn longer_runs_rank_higher_and_ties_go_below_earlier_runs() {
+        let mut scores = HighScores::default();
+
+        assert_eq!(scores.insert(run(60, 1)), Some(0));
+        assert_eq!(scores.insert(run(120, 2)), Some(0));
+        assert_eq!(scores.insert(run(60, 3)), Some(2));
+        assert_eq!(scores.insert(run(90, 4)), Some(1));
+        assert_eq!(seeds(&scores), [2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn full_tables_drop_their_shortest_run() {
+        let mut scores = HighScores::default();
+        for seed in 0..MAX_HIGH_SCORES as u64 {
+            scores.insert(run(100, seed));
+        }
+
+        // Ties with the last place, so there's no room for it
+        assert_eq!(scores.insert(run(100, 99)), None);
+        assert_eq!(scores.insert(run(1000, 42)), Some(0));
+
+        assert_eq!(scores.entries.len(), MAX_HIGH_SCORES);
+        assert_eq!(scores.entries[0].seed, 42);
+        assert!(!seeds(&scores).contains(&(MAX_HIGH_SCORES as u64 - 1)));
+    }
+}
@@ -4,6 +4,8 @@ use iyes_loopless::prelude::*;
 
 use crate::{
     consts::{SELECTION_COLLISION_GROUP, UNIT_COLLISION_GROUP},
+    pause::MatchState,
+    sim::FrameStage,
     utils::MousePosition,
     GameState,
 };
@@ -183,7 +185,20 @@ impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Events<ConfirmSelectionEvent>>()
             .add_enter_system(GameState::InGame, Self::init)
-            .add_system(Self::update.run_in_state(GameState::InGame))
-            .add_system(Self::box_select.run_in_state(GameState::InGame));
+            .add_system_to_stage(
+                FrameStage::Input,
+                Self::update
+                    .run_in_state(GameState::InGame)
+                    .run_in_state(MatchState::Running)
+                    .label("selection")
+                    .after("box_select"),
+            )
+            .add_system_to_stage(
+                FrameStage::Input,
+                Self::box_select
+                    .run_in_state(GameState::InGame)
+                    .run_in_state(MatchState::Running)
+                    .label("box_select"),
+            );
     }
 }
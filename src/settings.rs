@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{consts::SETTINGS_FILE, utils::save_dir};
+
+// Player preferences, kept next to the saves and written whenever they change
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub pause_on_focus_loss: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            pause_on_focus_loss: true,
+        }
+    }
+}
+
+impl Settings {
+    fn load() -> Self {
+        let path = save_dir().join(SETTINGS_FILE);
+        let Ok(text) = std::fs::read_to_string(&path) else { return Settings::default() };
+
+        ron::from_str(&text).unwrap_or_else(|err| {
+            warn!("Couldn't parse settings {:?}: {}", path, err);
+            Settings::default()
+        })
+    }
+
+    fn write(&self) {
+        let path = save_dir().join(SETTINGS_FILE);
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                std::fs::create_dir_all(save_dir())
+                    .and_then(|_| std::fs::write(&path, text))
+                    .map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            warn!("Couldn't save settings to {:?}: {}", path, err);
+        }
+    }
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn write_settings(settings: Res<Settings>) {
+        if settings.is_changed() && !settings.is_added() {
+            settings.write();
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::load())
+            .add_system(Self::write_settings);
+    }
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, transform::TransformSystem};
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::{
+    fixedtimestep::{FixedTimestepStageLabel, FixedTimesteps},
+    prelude::*,
+};
+
+use crate::consts::SIM_TIMESTEP;
+
+// Gameplay runs in this fixed timestep, so the outcome doesn't depend on frame rate.
+// Each tick runs the sub-stages below in order, with commands applied in between
+pub const SIM: &str = "sim";
+
+pub const SIM_PRE: usize = 0;
+// Player orders, spawning and the garden growing
+pub const SIM_COMMANDS: usize = 1;
+// Units deciding what to do about their commands and surroundings
+pub const SIM_AI: usize = 2;
+pub const SIM_MOVEMENT: usize = 3;
+// Everything that sends HealthChange
+pub const SIM_COMBAT: usize = 4;
+// Everything that reads HealthChange
+pub const SIM_HEALTH: usize = 5;
+pub const SIM_DEATH: usize = 6;
+pub const SIM_PHYSICS_SYNC: usize = 7;
+pub const SIM_PHYSICS_STEP: usize = 8;
+pub const SIM_PHYSICS_WRITEBACK: usize = 9;
+pub const SIM_LAST: usize = 10;
+
+// Frame rate stages around the simulation. Input is read before the ticks of a frame run,
+// UI and visuals go in the regular update afterwards
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub enum FrameStage {
+    Input,
+}
+
+// Stand-in for `Time` inside the simulation, every tick advances by exactly one timestep
+#[derive(Resource, Default)]
+pub struct SimTime {
+    pub tick: u64,
+}
+
+impl SimTime {
+    pub fn delta(&self) -> Duration {
+        Duration::from_secs_f32(SIM_TIMESTEP)
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        SIM_TIMESTEP
+    }
+}
+
+// Positions at the last two ticks, rendering blends between them
+#[derive(Component)]
+pub struct Interpolated {
+    previous: Vec3,
+    current: Vec3,
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    // Undo the render interpolation so the simulation only ever sees tick positions
+    fn restore_transforms(
+        q_interpolated: Query<(Entity, &Transform, Option<&Children>), With<Interpolated>>,
+        q_child: Query<&Transform>,
+        mut q_global: Query<&mut GlobalTransform>,
+    ) {
+        for (entity, transform, children) in &q_interpolated {
+            Self::set_global(entity, *transform, children, &q_child, &mut q_global);
+        }
+    }
+
+    fn snapshot_transforms(
+        mut cmd: Commands,
+        mut q_interpolated: Query<(&Transform, &mut Interpolated)>,
+        q_new: Query<(Entity, &Transform), Added<Velocity>>,
+    ) {
+        for (transform, mut interpolated) in &mut q_interpolated {
+            interpolated.previous = interpolated.current;
+            interpolated.current = transform.translation;
+        }
+
+        for (entity, transform) in &q_new {
+            cmd.entity(entity).insert(Interpolated {
+                previous: transform.translation,
+                current: transform.translation,
+            });
+        }
+    }
+
+    fn advance_tick(mut time: ResMut<SimTime>) {
+        time.tick += 1;
+    }
+
+    fn interpolate_transforms(
+        timesteps: Res<FixedTimesteps>,
+        q_interpolated: Query<(Entity, &Transform, &Interpolated, Option<&Children>)>,
+        q_child: Query<&Transform>,
+        mut q_global: Query<&mut GlobalTransform>,
+    ) {
+        let Some(info) = timesteps.get(SIM) else { return };
+        let alpha = info.overstep().min(1.0) as f32;
+
+        for (entity, transform, interpolated, children) in &q_interpolated {
+            let transform =
+                transform.with_translation(interpolated.previous.lerp(interpolated.current, alpha));
+            Self::set_global(entity, transform, children, &q_child, &mut q_global);
+        }
+    }
+
+    fn set_global(
+        entity: Entity,
+        transform: Transform,
+        children: Option<&Children>,
+        q_child: &Query<&Transform>,
+        q_global: &mut Query<&mut GlobalTransform>,
+    ) {
+        let global = GlobalTransform::from(transform);
+
+        if let Ok(mut entity_global) = q_global.get_mut(entity) {
+            *entity_global = global;
+        }
+
+        for child in children.into_iter().flatten() {
+            if let (Ok(child_transform), Ok(mut child_global)) =
+                (q_child.get(*child), q_global.get_mut(*child))
+            {
+                *child_global = global.mul_transform(*child_transform);
+            }
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        type Rapier = RapierPhysicsPlugin<NoUserData>;
+
+        app.init_resource::<SimTime>()
+            .add_fixed_timestep(Duration::from_secs_f32(SIM_TIMESTEP), SIM)
+            .add_stage_before(
+                FixedTimestepStageLabel(SIM),
+                FrameStage::Input,
+                SystemStage::parallel(),
+            );
+
+        // Gameplay stages run single threaded so systems within one always go in the same order
+        for _ in SIM_COMMANDS..=SIM_DEATH {
+            app.add_fixed_timestep_custom_child_stage(SIM, SystemStage::single_threaded());
+        }
+        for _ in SIM_PHYSICS_SYNC..=SIM_LAST {
+            app.add_fixed_timestep_child_stage(SIM);
+        }
+
+        app.add_fixed_timestep_system(SIM, SIM_PRE, Self::restore_transforms)
+            .add_fixed_timestep_system_set(
+                SIM,
+                SIM_PHYSICS_SYNC,
+                Rapier::get_systems(PhysicsStages::SyncBackend),
+            )
+            .add_fixed_timestep_system_set(
+                SIM,
+                SIM_PHYSICS_STEP,
+                Rapier::get_systems(PhysicsStages::StepSimulation),
+            )
+            .add_fixed_timestep_system_set(
+                SIM,
+                SIM_PHYSICS_WRITEBACK,
+                Rapier::get_systems(PhysicsStages::Writeback),
+            )
+            .add_fixed_timestep_system(SIM, SIM_LAST, Self::snapshot_transforms)
+            .add_fixed_timestep_system(SIM, SIM_LAST, Self::advance_tick)
+            // Removals have to be caught every frame, ticks don't run on all of them
+            .add_stage_before(
+                CoreStage::Last,
+                PhysicsStages::DetectDespawn,
+                SystemStage::parallel()
+                    .with_system_set(Rapier::get_systems(PhysicsStages::DetectDespawn)),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                Self::interpolate_transforms.after(TransformSystem::TransformPropagate),
+            );
+    }
+}
@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::{consts::GAME_SPEEDS, game_menu::SpeedText, GameState};
+
+// Scaling `Time` makes the simulation run more or fewer fixed ticks per frame, every tick
+// still advances by the same timestep so the outcome doesn't depend on the speed
+pub struct Plugin;
+
+impl Plugin {
+    fn change_speed(mut time: ResMut<Time>, keyboard: Res<Input<KeyCode>>) {
+        let keys = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+
+        for (key, speed) in keys.into_iter().zip(GAME_SPEEDS) {
+            if keyboard.just_pressed(key) {
+                time.set_relative_speed(speed);
+            }
+        }
+    }
+
+    fn reset_speed(mut time: ResMut<Time>) {
+        time.set_relative_speed(1.0);
+    }
+
+    fn update_speed_text(time: Res<Time>, mut q_text: Query<&mut Text, With<SpeedText>>) {
+        let mut text = q_text.single_mut();
+        text.sections[0].value = format!("Speed {}x", time.relative_speed());
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_exit_system(GameState::InGame, Self::reset_speed)
+            .add_system(Self::change_speed.run_in_state(GameState::InGame))
+            .add_system(Self::update_speed_text.run_in_state(GameState::InGame));
+    }
+}
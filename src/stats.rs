@@ -0,0 +1,254 @@
+use std::{collections::HashMap, hash::Hash};
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{SIM_TIMESTEP, STATS_SAMPLE_INTERVAL},
+    enemy::{EnemyKind, Nest},
+    faction::Faction,
+    game::{Compost, Rose},
+    health::{Dead, HealthChange},
+    plot::Crop,
+    sim::{SimTime, SIM, SIM_DEATH, SIM_HEALTH, SIM_LAST},
+    unit::Unit,
+    waves::WaveDirector,
+    GameState,
+};
+
+const GRAPH_WIDTH: f32 = 600.0;
+const GRAPH_HEIGHT: f32 = 150.0;
+
+// Running tally of the match for the breakdown at the end
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchStats {
+    pub planted: HashMap<Crop, u32>,
+    pub harvested: HashMap<Crop, u32>,
+    pub kills: HashMap<EnemyKind, u32>,
+    pub plots_unlocked: u32,
+    pub nests_destroyed: u32,
+    pub decayed: HashMap<Crop, u32>,
+    pub peak_army: u32,
+    pub rose_damage: f32,
+    // Army size against enemy count, every STATS_SAMPLE_INTERVAL seconds
+    pub timeline: Vec<(f32, u32, u32)>,
+}
+
+impl MatchStats {
+    pub fn total_kills(&self) -> u32 {
+        self.kills.values().sum()
+    }
+}
+
+fn tally<K: Eq + Hash>(
+    counts: &HashMap<K, u32>,
+    keys: impl IntoIterator<Item = K>,
+    name: impl Fn(&K) -> &'static str,
+) -> String {
+    keys.into_iter()
+        .map(|key| format!("{} {}", name(&key), counts.get(&key).unwrap_or(&0)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn spawn_stats(
+    parent: &mut ChildBuilder,
+    assets: &AssetServer,
+    stats: &MatchStats,
+    compost: &Compost,
+) {
+    let lines = [
+        format!(
+            "Harvested: {}",
+            tally(&stats.harvested, Crop::ALL, Crop::name)
+        ),
+        format!(
+            "Killed: {}",
+            tally(&stats.kills, EnemyKind::ALL, EnemyKind::name)
+        ),
+        format!("Compost earned {}, spent {}", compost.earned, compost.spent),
+        format!(
+            "Plots unlocked {}, crops left to decay {}",
+            stats.plots_unlocked,
+            stats.decayed.values().sum::<u32>()
+        ),
+        format!("Largest army {}", stats.peak_army),
+    ];
+
+    for line in lines {
+        parent.spawn(TextBundle::from_section(
+            line,
+            TextStyle {
+                font: assets.load("fonts/ModeSeven.ttf"),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        ));
+    }
+
+    spawn_graph(parent, stats);
+
+    parent.spawn(TextBundle::from_sections([
+        TextSection::new(
+            "Army",
+            TextStyle {
+                font: assets.load("fonts/ModeSeven.ttf"),
+                font_size: 20.0,
+                color: Color::GREEN,
+            },
+        ),
+        TextSection::new(
+            " against ",
+            TextStyle {
+                font: assets.load("fonts/ModeSeven.ttf"),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        ),
+        TextSection::new(
+            "pests",
+            TextStyle {
+                font: assets.load("fonts/ModeSeven.ttf"),
+                font_size: 20.0,
+                color: Color::RED,
+            },
+        ),
+    ]));
+}
+
+// One column per sample, the two counts drawn over each other
+fn spawn_graph(parent: &mut ChildBuilder, stats: &MatchStats) {
+    let count = stats.timeline.len().max(1);
+    let width = GRAPH_WIDTH / count as f32;
+    let duration = stats
+        .timeline
+        .last()
+        .map_or(0.0, |(seconds, _, _)| *seconds)
+        .max(f32::EPSILON);
+    let max = stats
+        .timeline
+        .iter()
+        .map(|(_, army, pests)| *army.max(pests))
+        .max()
+        .unwrap_or(0)
+        .max(1) as f32;
+
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Px(GRAPH_WIDTH),
+                    height: Val::Px(GRAPH_HEIGHT),
+                },
+                margin: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            background_color: Color::rgba(1.0, 1.0, 1.0, 0.1).into(),
+            ..default()
+        })
+        .with_children(|graph| {
+            for &(seconds, army, pests) in &stats.timeline {
+                let left = seconds / duration * (GRAPH_WIDTH - width);
+
+                for (value, color) in [
+                    (army, Color::rgba(0.0, 1.0, 0.0, 0.6)),
+                    (pests, Color::rgba(1.0, 0.0, 0.0, 0.6)),
+                ] {
+                    graph.spawn(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                left: Val::Px(left),
+                                bottom: Val::Px(0.0),
+                                ..default()
+                            },
+                            size: Size {
+                                width: Val::Px(width),
+                                height: Val::Px(value as f32 / max * GRAPH_HEIGHT),
+                            },
+                            ..default()
+                        },
+                        background_color: color.into(),
+                        ..default()
+                    });
+                }
+            }
+        });
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn reset(mut cmd: Commands) {
+        cmd.insert_resource(MatchStats::default());
+    }
+
+    fn count_kills(
+        mut stats: ResMut<MatchStats>,
+        q_dead: Query<&EnemyKind, Added<Dead>>,
+        q_nest: Query<(), (With<Nest>, Added<Dead>)>,
+    ) {
+        for kind in &q_dead {
+            *stats.kills.entry(*kind).or_default() += 1;
+        }
+        stats.nests_destroyed += q_nest.iter().count() as u32;
+    }
+
+    fn count_rose_damage(
+        mut stats: ResMut<MatchStats>,
+        mut events: EventReader<HealthChange>,
+        q_rose: Query<(), With<Rose>>,
+    ) {
+        for event in events.iter() {
+            if event.amount < 0.0 && q_rose.contains(event.target) {
+                stats.rose_damage -= event.amount;
+            }
+        }
+    }
+
+    fn sample(
+        mut stats: ResMut<MatchStats>,
+        time: Res<SimTime>,
+        director: Res<WaveDirector>,
+        q_unit: Query<&Faction, (With<Unit>, Without<Dead>)>,
+    ) {
+        let army = q_unit
+            .iter()
+            .filter(|faction| **faction == Faction::Garden)
+            .count() as u32;
+        stats.peak_army = stats.peak_army.max(army);
+
+        let interval = (STATS_SAMPLE_INTERVAL / SIM_TIMESTEP).round() as u64;
+        if interval == 0 || time.tick % interval != 0 {
+            return;
+        }
+
+        let pests = q_unit
+            .iter()
+            .filter(|faction| **faction == Faction::Pests)
+            .count() as u32;
+        stats
+            .timeline
+            .push((director.total.as_secs_f32(), army, pests));
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatchStats>()
+            .add_enter_system(GameState::InGame, Self::reset)
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_DEATH,
+                Self::count_kills.run_in_state(GameState::InGame),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_HEALTH,
+                Self::count_rose_damage.run_in_state(GameState::InGame),
+            )
+            .add_fixed_timestep_system(SIM, SIM_LAST, Self::sample.run_in_state(GameState::InGame));
+    }
+}
@@ -0,0 +1,326 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    consts::PLOT_SIZE,
+    plot::{Crop, Plot},
+    replay::{PendingActions, PlayerAction},
+    selection::Selectable,
+    sim::FrameStage,
+    stats::MatchStats,
+    GameState,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    PlantCarrot,
+    WaitForGrowth,
+    Harvest,
+    Select,
+    Command,
+    Ready,
+    Done,
+}
+
+impl Step {
+    fn prompt(&self) -> &'static str {
+        match self {
+            Step::PlantCarrot => "Click the highlighted plot and plant a carrot",
+            Step::WaitForGrowth => "Crops take a while to grow, wait for the carrot to ripen",
+            Step::Harvest => "The carrot is ready, click the plot again to harvest it",
+            Step::Select => "Harvested crops fight for you, drag a box around them to select them",
+            Step::Command => "Right click to move them, or right click a pest to attack it",
+            Step::Ready => {
+                "Pests will come from the nests to eat the rose. Press Enter when you're ready"
+            }
+            Step::Done => "",
+        }
+    }
+
+    fn next(&self) -> Step {
+        match self {
+            Step::PlantCarrot => Step::WaitForGrowth,
+            Step::WaitForGrowth => Step::Harvest,
+            Step::Harvest => Step::Select,
+            Step::Select => Step::Command,
+            Step::Command => Step::Ready,
+            Step::Ready | Step::Done => Step::Done,
+        }
+    }
+}
+
+// Present while the tutorial is being played, from the menu button until back at the menu
+#[derive(Resource)]
+pub struct Tutorial {
+    pub step: Step,
+}
+
+impl Default for Tutorial {
+    fn default() -> Self {
+        Tutorial {
+            step: Step::PlantCarrot,
+        }
+    }
+}
+
+// Waves don't start until the player has been through every step
+pub fn waves_held(tutorial: Option<Res<Tutorial>>) -> bool {
+    tutorial.is_some_and(|tutorial| tutorial.step != Step::Done)
+}
+
+#[derive(Component)]
+pub struct Prompt;
+
+#[derive(Component)]
+pub struct PromptText;
+
+#[derive(Component)]
+pub struct Highlight;
+
+pub struct Plugin;
+
+impl Plugin {
+    fn init(mut cmd: Commands, assets: Res<AssetServer>, mut tutorial: ResMut<Tutorial>) {
+        *tutorial = Tutorial::default();
+
+        cmd.spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(40.0),
+                    ..default()
+                },
+                size: Size {
+                    width: Val::Percent(100.0),
+                    height: Val::Auto,
+                },
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(8.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                        ..default()
+                    },
+                    Prompt,
+                ))
+                .with_children(|prompt| {
+                    prompt.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: assets.load("fonts/ModeSeven.ttf"),
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                            },
+                        ),
+                        PromptText,
+                    ));
+                });
+        });
+
+        cmd.spawn((
+            SpriteBundle {
+                texture: assets.load("arrow.png"),
+                sprite: Sprite {
+                    anchor: bevy::sprite::Anchor::TopCenter,
+                    color: Color::YELLOW,
+                    ..default()
+                },
+                visibility: Visibility::INVISIBLE,
+                ..default()
+            },
+            Highlight,
+        ));
+    }
+
+    fn remove(mut cmd: Commands) {
+        cmd.remove_resource::<Tutorial>();
+    }
+
+    // Runs before the simulation picks up this frame's orders, so they can still be seen. Plot
+    // and harvest events are used up within the tick that sends them, so those steps go by the
+    // state they leave behind instead
+    fn advance(
+        mut tutorial: ResMut<Tutorial>,
+        pending: Res<PendingActions>,
+        stats: Res<MatchStats>,
+        keyboard: Res<Input<KeyCode>>,
+        q_plot: Query<&Plot>,
+        q_selectable: Query<&Selectable>,
+    ) {
+        let done = match tutorial.step {
+            Step::PlantCarrot => q_plot
+                .iter()
+                .any(|plot| matches!(plot, Plot::Growing(Crop::Carrot, _))),
+            Step::WaitForGrowth => q_plot.iter().any(|plot| matches!(plot, Plot::Ready(..))),
+            Step::Harvest => stats.harvested.values().sum::<u32>() > 0,
+            Step::Select => q_selectable.iter().any(|selectable| selectable.selected),
+            Step::Command => pending
+                .0
+                .iter()
+                .any(|action| matches!(action, PlayerAction::Command { .. })),
+            Step::Ready => keyboard.just_pressed(KeyCode::Return),
+            Step::Done => false,
+        };
+
+        if done {
+            tutorial.step = tutorial.step.next();
+        }
+    }
+
+    fn update_prompt(
+        tutorial: Res<Tutorial>,
+        mut q_prompt: Query<&mut Style, With<Prompt>>,
+        mut q_text: Query<&mut Text, With<PromptText>>,
+    ) {
+        if !tutorial.is_changed() {
+            return;
+        }
+        let Ok(mut style) = q_prompt.get_single_mut() else { return };
+        let Ok(mut text) = q_text.get_single_mut() else { return };
+
+        text.sections[0].value = tutorial.step.prompt().to_string();
+        style.display = if tutorial.step == Step::Done {
+            Display::None
+        } else {
+            Display::Flex
+        };
+    }
+
+    // Points at the plot the current step is about
+    fn update_highlight(
+        time: Res<Time>,
+        tutorial: Res<Tutorial>,
+        q_plot: Query<(&Plot, &GlobalTransform)>,
+        mut q_highlight: Query<(&mut Transform, &mut Visibility), With<Highlight>>,
+    ) {
+        let Ok((mut transform, mut visibility)) = q_highlight.get_single_mut() else { return };
+
+        let target = q_plot
+            .iter()
+            .find(|(plot, _)| match tutorial.step {
+                Step::PlantCarrot => matches!(plot, Plot::Empty),
+                Step::Harvest => matches!(plot, Plot::Ready(..)),
+                _ => false,
+            })
+            .map(|(_, plot_transform)| plot_transform.translation().truncate());
+
+        visibility.is_visible = target.is_some();
+
+        if let Some(pos) = target {
+            let bob = (time.elapsed_seconds() * 4.0).sin() * 2.0;
+            transform.translation =
+                (pos - Vec2::new(0.0, PLOT_SIZE.y / 2.0 + 2.0 + bob)).extend(1.0);
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_enter_system(
+            GameState::InGame,
+            Self::init.run_if_resource_exists::<Tutorial>(),
+        )
+        .add_enter_system(GameState::MainMenu, Self::remove)
+        .add_system_to_stage(
+            FrameStage::Input,
+            Self::advance
+                .run_in_state(GameState::InGame)
+                .run_if_resource_exists::<Tutorial>()
+                .after("unit_command"),
+        )
+        .add_system(
+            Self::update_prompt
+                .run_in_state(GameState::InGame)
+                .run_if_resource_exists::<Tutorial>(),
+        )
+        .add_system(
+            Self::update_highlight
+                .run_in_state(GameState::InGame)
+                .run_if_resource_exists::<Tutorial>(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ActionCommand;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Tutorial>()
+            .init_resource::<PendingActions>()
+            .init_resource::<MatchStats>()
+            .init_resource::<Input<KeyCode>>()
+            .add_system(Plugin::advance);
+        app
+    }
+
+    fn step(app: &App) -> Step {
+        app.world.resource::<Tutorial>().step
+    }
+
+    fn set_plot(app: &mut App, plot: Entity, state: Plot) {
+        *app.world.get_mut::<Plot>(plot).unwrap() = state;
+        app.update();
+    }
+
+    #[test]
+    fn steps_go_by_what_the_player_has_done() {
+        let mut app = app();
+        let plot = app.world.spawn(Plot::Empty).id();
+        app.update();
+        assert_eq!(step(&app), Step::PlantCarrot);
+
+        // Only a carrot will do
+        set_plot(&mut app, plot, Plot::Growing(Crop::Wheat, 0.0));
+        assert_eq!(step(&app), Step::PlantCarrot);
+        set_plot(&mut app, plot, Plot::Growing(Crop::Carrot, 0.0));
+        assert_eq!(step(&app), Step::WaitForGrowth);
+        set_plot(&mut app, plot, Plot::Ready(Crop::Carrot, 0.0));
+        assert_eq!(step(&app), Step::Harvest);
+
+        // Composting the carrot empties the plot too, but doesn't give any units to select
+        set_plot(&mut app, plot, Plot::Empty);
+        assert_eq!(step(&app), Step::Harvest);
+        app.world
+            .resource_mut::<MatchStats>()
+            .harvested
+            .insert(Crop::Carrot, 1);
+        app.update();
+        assert_eq!(step(&app), Step::Select);
+
+        app.world.spawn(Selectable {
+            selected: true,
+            ..default()
+        });
+        app.update();
+        assert_eq!(step(&app), Step::Command);
+
+        app.world
+            .resource_mut::<PendingActions>()
+            .0
+            .push(PlayerAction::Command {
+                units: Vec::new(),
+                command: ActionCommand::Move([0.0, 0.0]),
+            });
+        app.update();
+        assert_eq!(step(&app), Step::Ready);
+
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .press(KeyCode::Return);
+        app.update();
+        assert_eq!(step(&app), Step::Done);
+    }
+}
@@ -4,10 +4,17 @@ use iyes_loopless::prelude::*;
 
 use crate::{
     consts::*,
+    faction::{Faction, Provoked, Relation, Relations},
     game::Rose,
     health::{Dead, Health, HealthBar, HealthChange},
+    match_settings::MatchSettings,
+    pause::MatchState,
     plot::{Crop, HarvestEvent},
+    replay::{ActionCommand, PendingActions, PlayerAction, SimId},
+    rng::{GameRng, RngStream},
     selection::{HoverIndicator, Selectable, SelectionIndicator},
+    sim::{FrameStage, SimTime, SIM, SIM_AI, SIM_COMBAT, SIM_COMMANDS, SIM_MOVEMENT},
+    stats::MatchStats,
     utils::{Bar, MousePosition, PlaySound},
     GameState,
 };
@@ -48,6 +55,24 @@ impl Unit {
             ..default()
         }
     }
+
+    pub fn command(&self) -> Option<&UnitCommand> {
+        self.command.as_ref()
+    }
+
+    // No orders left and nothing to fight
+    pub fn is_idle(&self) -> bool {
+        self.command.is_none() && matches!(self.state, UnitState::Idle)
+    }
+
+    pub fn set_command(&mut self, command: UnitCommand) {
+        self.command = Some(command);
+    }
+
+    pub fn scale(&mut self, speed: f32, damage: f32) {
+        self.move_speed *= speed;
+        self.damage *= damage;
+    }
 }
 
 #[derive(Default)]
@@ -60,39 +85,155 @@ enum UnitState {
 }
 
 #[derive(Clone)]
-enum UnitCommand {
+pub enum UnitCommand {
     Move(Vec2),
     AttackMove(Vec2),
-}
-
-trait Side {
-    const ATTACKS_GROUP: Group;
+    Attack(Entity),
 }
 
 #[derive(Component)]
 pub struct Enemy;
 
-impl Side for Enemy {
-    const ATTACKS_GROUP: Group = FRIENDLY_COLLISION_GROUP;
-}
-
-#[derive(Component, Clone)]
-pub struct Friendly;
-
-impl Side for Friendly {
-    const ATTACKS_GROUP: Group = ENEMY_COLLISION_GROUP;
+pub fn spawn_crop(cmd: &mut Commands, assets: &AssetServer, crop: Crop, pos: Vec2) -> Entity {
+    let (texture, radius, unit, health) = match crop {
+        Crop::Carrot => (
+            "carrot_unit.png",
+            4.0,
+            Unit::new(
+                CARROT_MOVE_SPEED,
+                CARROT_AGGRO_RANGE,
+                CARROT_CHASE_RANGE,
+                CARROT_ATTACK_RANGE,
+                CARROT_LEASH_RANGE,
+                CARROT_ATTACK_SPEED,
+                CARROT_DAMAGE,
+            ),
+            CARROT_HEALTH,
+        ),
+        Crop::Clover => (
+            "clover_unit.png",
+            2.0,
+            Unit::new(
+                CLOVER_MOVE_SPEED,
+                CLOVER_AGGRO_RANGE,
+                CLOVER_CHASE_RANGE,
+                CLOVER_ATTACK_RANGE,
+                CLOVER_LEASH_RANGE,
+                CLOVER_ATTACK_SPEED,
+                CLOVER_DAMAGE,
+            ),
+            CLOVER_HEALTH,
+        ),
+        Crop::Wheat => (
+            "wheat_unit.png",
+            4.0,
+            Unit::new(
+                WHEAT_MOVE_SPEED,
+                WHEAT_AGGRO_RANGE,
+                WHEAT_CHASE_RANGE,
+                WHEAT_ATTACK_RANGE,
+                WHEAT_LEASH_RANGE,
+                WHEAT_ATTACK_SPEED,
+                WHEAT_DAMAGE,
+            ),
+            WHEAT_HEALTH,
+        ),
+    };
+
+    cmd.spawn((
+        SpriteBundle {
+            texture: assets.load(texture),
+            transform: Transform::from_translation(pos.extend(0.1)),
+            sprite: Sprite {
+                anchor: bevy::sprite::Anchor::BottomCenter,
+                ..default()
+            },
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Velocity::default(),
+        Collider::ball(radius),
+        Friction {
+            coefficient: 0.0,
+            combine_rule: CoefficientCombineRule::Min,
+        },
+        LockedAxes::ROTATION_LOCKED_Z,
+        CollisionGroups {
+            memberships: SELECTION_COLLISION_GROUP | UNIT_COLLISION_GROUP | Faction::Garden.group(),
+            filters: UNIT_COLLISION_GROUP,
+        },
+        Damping {
+            linear_damping: 20.0,
+            angular_damping: 0.0,
+        },
+        unit,
+        Health::new(health),
+        Selectable::default(),
+        Faction::Garden,
+        crop,
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            SpriteBundle {
+                texture: assets.load("arrow.png"),
+                transform: Transform::from_translation(Vec3::new(0.0, -5.0, 0.1)),
+                sprite: Sprite {
+                    anchor: bevy::sprite::Anchor::TopCenter,
+                    color: Color::YELLOW,
+                    ..default()
+                },
+                visibility: Visibility::INVISIBLE,
+                ..default()
+            },
+            SelectionIndicator,
+        ));
+        parent.spawn((
+            SpriteBundle {
+                texture: assets.load("arrow.png"),
+                transform: Transform::from_translation(Vec3::new(0.0, -5.0, 0.1)),
+                sprite: Sprite {
+                    anchor: bevy::sprite::Anchor::TopCenter,
+                    ..default()
+                },
+                visibility: Visibility::INVISIBLE,
+                ..default()
+            },
+            HoverIndicator,
+        ));
+        parent.spawn((
+            SpriteBundle {
+                transform: Transform::from_translation(Vec3::new(0.0, -4.0, 0.1)),
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(2.0, 1.0)),
+                    color: Color::RED,
+                    ..default()
+                },
+                ..default()
+            },
+            Bar {
+                value: health,
+                max: health,
+                size: 10.0,
+            },
+            HealthBar,
+        ));
+    })
+    .id()
 }
 
 pub struct Plugin;
 
 impl Plugin {
-    fn update_unit_state<T: Side + Component>(
+    fn update_unit_state(
         rapier_ctx: Res<RapierContext>,
-        mut q_unit: Query<(&mut Unit, &GlobalTransform), With<T>>,
+        relations: Res<Relations>,
+        mut q_unit: Query<(&mut Unit, &GlobalTransform, &Faction, Option<&Provoked>)>,
         q_transform: Query<(&GlobalTransform, Option<&Rose>), Without<Dead>>,
     ) {
-        for (mut unit, transform) in &mut q_unit {
+        for (mut unit, transform, faction, provoked) in &mut q_unit {
             let unit_pos = transform.translation().truncate();
+            let attacks = relations.hostile_groups(*faction)
+                | provoked.map_or(Group::NONE, |provoked| provoked.groups);
             if let Some(command) = &unit.command {
                 match command {
                     UnitCommand::Move(dest) => {
@@ -136,7 +277,7 @@ impl Plugin {
                                     &Collider::ball(unit.chase_range),
                                     QueryFilter::new().groups(InteractionGroups {
                                         memberships: UNIT_COLLISION_GROUP.bits().into(),
-                                        filter: T::ATTACKS_GROUP.bits().into(),
+                                        filter: attacks.bits().into(),
                                     }),
                                     |e| {
                                         if let Ok((target, rose)) = q_transform.get(e) {
@@ -183,7 +324,7 @@ impl Plugin {
                                         &Collider::ball(unit.chase_range),
                                         QueryFilter::new().groups(InteractionGroups {
                                             memberships: UNIT_COLLISION_GROUP.bits().into(),
-                                            filter: T::ATTACKS_GROUP.bits().into(),
+                                            filter: attacks.bits().into(),
                                         }),
                                         |e| {
                                             if entity == e {
@@ -205,7 +346,7 @@ impl Plugin {
                                             &Collider::ball(unit.attack_range),
                                             QueryFilter::new().groups(InteractionGroups {
                                                 memberships: UNIT_COLLISION_GROUP.bits().into(),
-                                                filter: T::ATTACKS_GROUP.bits().into(),
+                                                filter: attacks.bits().into(),
                                             }),
                                             |e| {
                                                 if entity == e {
@@ -229,6 +370,51 @@ impl Plugin {
                             }
                         }
                     }
+                    UnitCommand::Attack(target) => {
+                        let target = *target;
+                        let Ok((target_transform, _)) = q_transform.get(target) else {
+                            unit.last_target_pos = unit_pos;
+                            unit.leash_pos = unit_pos;
+                            unit.command = None;
+                            unit.state = UnitState::Idle;
+                            continue;
+                        };
+
+                        match unit.state {
+                            UnitState::Attack(_) => {
+                                if unit.attack_timer.finished() {
+                                    unit.attack_timer.reset();
+                                    unit.state = UnitState::Chase(target);
+                                }
+                            }
+                            _ => {
+                                // Explicit targets ignore faction relations, so check range directly
+                                let mut in_attack_range = false;
+
+                                rapier_ctx.intersections_with_shape(
+                                    unit_pos,
+                                    0.0,
+                                    &Collider::ball(unit.attack_range),
+                                    QueryFilter::new(),
+                                    |e| {
+                                        if target == e {
+                                            in_attack_range = true;
+                                            return false;
+                                        }
+                                        true
+                                    },
+                                );
+
+                                unit.last_target_pos = target_transform.translation().truncate();
+                                unit.leash_pos = unit.last_target_pos;
+                                if in_attack_range {
+                                    unit.state = UnitState::Attack(target);
+                                } else {
+                                    unit.state = UnitState::Chase(target);
+                                }
+                            }
+                        }
+                    }
                 }
             } else {
                 match unit.state {
@@ -240,7 +426,7 @@ impl Plugin {
                             &Collider::ball(unit.aggro_range),
                             QueryFilter::new().groups(InteractionGroups {
                                 memberships: UNIT_COLLISION_GROUP.bits().into(),
-                                filter: T::ATTACKS_GROUP.bits().into(),
+                                filter: attacks.bits().into(),
                             }),
                             |e| {
                                 if let Ok((target, rose)) = q_transform.get(e) {
@@ -284,7 +470,7 @@ impl Plugin {
                             &Collider::ball(unit.aggro_range),
                             QueryFilter::new().groups(InteractionGroups {
                                 memberships: UNIT_COLLISION_GROUP.bits().into(),
-                                filter: T::ATTACKS_GROUP.bits().into(),
+                                filter: attacks.bits().into(),
                             }),
                             |e| {
                                 if let Ok((target, rose)) = q_transform.get(e) {
@@ -322,7 +508,7 @@ impl Plugin {
                                 &Collider::ball(unit.chase_range),
                                 QueryFilter::new().groups(InteractionGroups {
                                     memberships: UNIT_COLLISION_GROUP.bits().into(),
-                                    filter: T::ATTACKS_GROUP.bits().into(),
+                                    filter: attacks.bits().into(),
                                 }),
                                 |e| {
                                     if entity == e {
@@ -344,7 +530,7 @@ impl Plugin {
                                     &Collider::ball(unit.attack_range),
                                     QueryFilter::new().groups(InteractionGroups {
                                         memberships: UNIT_COLLISION_GROUP.bits().into(),
-                                        filter: T::ATTACKS_GROUP.bits().into(),
+                                        filter: attacks.bits().into(),
                                     }),
                                     |e| {
                                         if entity == e {
@@ -371,14 +557,11 @@ impl Plugin {
         }
     }
 
-    fn process_unit_state(
-        mut q_unit: Query<(&mut Velocity, &mut Unit, &GlobalTransform)>,
+    fn move_units(
+        mut q_unit: Query<(&mut Velocity, &Unit, &GlobalTransform)>,
         q_transform: Query<&GlobalTransform>,
-        time: Res<Time>,
-        mut damage: EventWriter<HealthChange>,
     ) {
-        for (mut velocity, mut unit, transform) in &mut q_unit {
-            unit.attack_timer.tick(time.delta());
+        for (mut velocity, unit, transform) in &mut q_unit {
             match unit.state {
                 UnitState::Idle => {}
                 UnitState::Move(dest) => {
@@ -394,13 +577,26 @@ impl Plugin {
                             * unit.move_speed;
                     };
                 }
-                UnitState::Attack(entity) => {
-                    if unit.attack_timer.finished() {
-                        damage.send(HealthChange {
-                            target: entity,
-                            amount: -unit.damage,
-                        });
-                    }
+                UnitState::Attack(_) => {}
+            }
+        }
+    }
+
+    fn attack_units(
+        mut q_unit: Query<(Entity, &mut Unit)>,
+        time: Res<SimTime>,
+        mut damage: EventWriter<HealthChange>,
+    ) {
+        for (source, mut unit) in &mut q_unit {
+            unit.attack_timer.tick(time.delta());
+
+            if let UnitState::Attack(entity) = unit.state {
+                if unit.attack_timer.finished() {
+                    damage.send(HealthChange {
+                        target: entity,
+                        amount: -unit.damage,
+                        source: Some(source),
+                    });
                 }
             }
         }
@@ -411,326 +607,110 @@ impl Plugin {
         mut ev_harvest: ResMut<Events<HarvestEvent>>,
         mut ev_sound: EventWriter<PlaySound>,
         assets: Res<AssetServer>,
+        rng: Res<GameRng>,
+        mut stats: ResMut<MatchStats>,
     ) {
-        let rng = fastrand::Rng::default();
+        let rng = rng.stream(RngStream::Harvest);
         for harvest in ev_harvest.drain() {
             ev_sound.send(PlaySound("snip.ogg".to_string()));
-            let common = (
-                RigidBody::Dynamic,
-                Velocity::default(),
-                Friction {
-                    coefficient: 0.0,
-                    combine_rule: CoefficientCombineRule::Min,
-                },
-                LockedAxes::ROTATION_LOCKED_Z,
-                CollisionGroups {
-                    memberships: SELECTION_COLLISION_GROUP
-                        | UNIT_COLLISION_GROUP
-                        | FRIENDLY_COLLISION_GROUP,
-                    filters: UNIT_COLLISION_GROUP,
-                },
-                Damping {
-                    linear_damping: 20.0,
-                    angular_damping: 0.0,
-                },
-                Selectable::default(),
-                Friendly,
-                harvest.crop.clone(),
-            );
-
-            let mut pos = harvest.pos;
-            pos.z = 0.1;
-            match harvest.crop {
-                Crop::Carrot => {
-                    for _ in 0..CARROT_COUNT {
-                        let offset =
-                            (Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5) * PLOT_SIZE).extend(0.0);
-                        cmd.spawn((
-                            SpriteBundle {
-                                texture: assets.load("carrot_unit.png"),
-                                transform: Transform::from_translation(pos + offset),
-                                sprite: Sprite {
-                                    anchor: bevy::sprite::Anchor::BottomCenter,
-                                    ..default()
-                                },
-                                ..default()
-                            },
-                            Collider::ball(4.0),
-                            Unit::new(
-                                CARROT_MOVE_SPEED,
-                                CARROT_AGGRO_RANGE,
-                                CARROT_CHASE_RANGE,
-                                CARROT_ATTACK_RANGE,
-                                CARROT_LEASH_RANGE,
-                                CARROT_ATTACK_SPEED,
-                                CARROT_DAMAGE,
-                            ),
-                            Health::new(CARROT_HEALTH),
-                            common.clone(),
-                        ))
-                        .with_children(|parent| {
-                            parent.spawn((
-                                SpriteBundle {
-                                    texture: assets.load("arrow.png"),
-                                    transform: Transform::from_translation(Vec3::new(
-                                        0.0, -5.0, 0.1,
-                                    )),
-                                    sprite: Sprite {
-                                        anchor: bevy::sprite::Anchor::TopCenter,
-                                        color: Color::YELLOW,
-                                        ..default()
-                                    },
-                                    visibility: Visibility::INVISIBLE,
-                                    ..default()
-                                },
-                                SelectionIndicator,
-                            ));
-                            parent.spawn((
-                                SpriteBundle {
-                                    texture: assets.load("arrow.png"),
-                                    transform: Transform::from_translation(Vec3::new(
-                                        0.0, -5.0, 0.1,
-                                    )),
-                                    sprite: Sprite {
-                                        anchor: bevy::sprite::Anchor::TopCenter,
-                                        ..default()
-                                    },
-                                    visibility: Visibility::INVISIBLE,
-                                    ..default()
-                                },
-                                HoverIndicator,
-                            ));
-                            parent.spawn((
-                                SpriteBundle {
-                                    transform: Transform::from_translation(Vec3::new(
-                                        0.0, -4.0, 0.1,
-                                    )),
-                                    sprite: Sprite {
-                                        custom_size: Some(Vec2::new(2.0, 1.0)),
-                                        color: Color::RED,
-                                        ..default()
-                                    },
-                                    ..default()
-                                },
-                                Bar {
-                                    value: CARROT_HEALTH,
-                                    max: CARROT_HEALTH,
-                                    size: 10.0,
-                                },
-                                HealthBar,
-                            ));
-                        });
-                    }
-                }
-                Crop::Clover => {
-                    for _ in 0..CLOVER_COUNT {
-                        let offset =
-                            (Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5) * PLOT_SIZE).extend(0.0);
-                        cmd.spawn((
-                            SpriteBundle {
-                                texture: assets.load("clover_unit.png"),
-                                transform: Transform::from_translation(pos + offset),
-                                sprite: Sprite {
-                                    anchor: bevy::sprite::Anchor::BottomCenter,
-                                    ..default()
-                                },
-                                ..default()
-                            },
-                            Collider::ball(2.0),
-                            Unit::new(
-                                CLOVER_MOVE_SPEED,
-                                CLOVER_AGGRO_RANGE,
-                                CLOVER_CHASE_RANGE,
-                                CLOVER_ATTACK_RANGE,
-                                CLOVER_LEASH_RANGE,
-                                CLOVER_ATTACK_SPEED,
-                                CLOVER_DAMAGE,
-                            ),
-                            Health::new(CLOVER_HEALTH),
-                            common.clone(),
-                        ))
-                        .with_children(|parent| {
-                            parent.spawn((
-                                SpriteBundle {
-                                    texture: assets.load("arrow.png"),
-                                    transform: Transform::from_translation(Vec3::new(
-                                        0.0, -5.0, 0.1,
-                                    )),
-                                    sprite: Sprite {
-                                        anchor: bevy::sprite::Anchor::TopCenter,
-                                        color: Color::YELLOW,
-                                        ..default()
-                                    },
-                                    visibility: Visibility::INVISIBLE,
-                                    ..default()
-                                },
-                                SelectionIndicator,
-                            ));
-                            parent.spawn((
-                                SpriteBundle {
-                                    texture: assets.load("arrow.png"),
-                                    transform: Transform::from_translation(Vec3::new(
-                                        0.0, -5.0, 0.1,
-                                    )),
-                                    sprite: Sprite {
-                                        anchor: bevy::sprite::Anchor::TopCenter,
-                                        ..default()
-                                    },
-                                    visibility: Visibility::INVISIBLE,
-                                    ..default()
-                                },
-                                HoverIndicator,
-                            ));
-                            parent.spawn((
-                                SpriteBundle {
-                                    transform: Transform::from_translation(Vec3::new(
-                                        0.0, -4.0, 0.1,
-                                    )),
-                                    sprite: Sprite {
-                                        custom_size: Some(Vec2::new(2.0, 1.0)),
-                                        color: Color::RED,
-                                        ..default()
-                                    },
-                                    ..default()
-                                },
-                                Bar {
-                                    value: CLOVER_HEALTH,
-                                    max: CLOVER_HEALTH,
-                                    size: 10.0,
-                                },
-                                HealthBar,
-                            ));
-                        });
-                    }
-                }
 
-                Crop::Wheat => {
-                    for _ in 0..WHEAT_COUNT {
-                        let offset =
-                            (Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5) * PLOT_SIZE).extend(0.0);
-                        cmd.spawn((
-                            SpriteBundle {
-                                texture: assets.load("wheat_unit.png"),
-                                transform: Transform::from_translation(pos + offset),
-                                sprite: Sprite {
-                                    anchor: bevy::sprite::Anchor::BottomCenter,
-                                    ..default()
-                                },
-                                ..default()
-                            },
-                            Collider::ball(4.0),
-                            Unit::new(
-                                WHEAT_MOVE_SPEED,
-                                WHEAT_AGGRO_RANGE,
-                                WHEAT_CHASE_RANGE,
-                                WHEAT_ATTACK_RANGE,
-                                WHEAT_LEASH_RANGE,
-                                WHEAT_ATTACK_SPEED,
-                                WHEAT_DAMAGE,
-                            ),
-                            Health::new(WHEAT_HEALTH),
-                            common.clone(),
-                        ))
-                        .with_children(|parent| {
-                            parent.spawn((
-                                SpriteBundle {
-                                    texture: assets.load("arrow.png"),
-                                    transform: Transform::from_translation(Vec3::new(
-                                        0.0, -5.0, 0.1,
-                                    )),
-                                    sprite: Sprite {
-                                        anchor: bevy::sprite::Anchor::TopCenter,
-                                        color: Color::YELLOW,
-                                        ..default()
-                                    },
-                                    visibility: Visibility::INVISIBLE,
-                                    ..default()
-                                },
-                                SelectionIndicator,
-                            ));
-                            parent.spawn((
-                                SpriteBundle {
-                                    texture: assets.load("arrow.png"),
-                                    transform: Transform::from_translation(Vec3::new(
-                                        0.0, -5.0, 0.1,
-                                    )),
-                                    sprite: Sprite {
-                                        anchor: bevy::sprite::Anchor::TopCenter,
-                                        ..default()
-                                    },
-                                    visibility: Visibility::INVISIBLE,
-                                    ..default()
-                                },
-                                HoverIndicator,
-                            ));
-                            parent.spawn((
-                                SpriteBundle {
-                                    transform: Transform::from_translation(Vec3::new(
-                                        0.0, -4.0, 0.1,
-                                    )),
-                                    sprite: Sprite {
-                                        custom_size: Some(Vec2::new(2.0, 1.0)),
-                                        color: Color::RED,
-                                        ..default()
-                                    },
-                                    ..default()
-                                },
-                                Bar {
-                                    value: WHEAT_HEALTH,
-                                    max: WHEAT_HEALTH,
-                                    size: 10.0,
-                                },
-                                HealthBar,
-                            ));
-                        });
-                    }
-                }
+            let count = match harvest.crop {
+                Crop::Carrot => CARROT_COUNT,
+                Crop::Clover => CLOVER_COUNT,
+                Crop::Wheat => WHEAT_COUNT,
+            };
+            *stats.harvested.entry(harvest.crop.clone()).or_default() += count;
+
+            for _ in 0..count {
+                let offset = Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5) * PLOT_SIZE;
+                spawn_crop(
+                    &mut cmd,
+                    &assets,
+                    harvest.crop.clone(),
+                    harvest.pos.truncate() + offset,
+                );
             }
         }
     }
 
-    fn enemy_spawn(mut q_enemy: Query<&mut Unit, Changed<Enemy>>) {
+    fn enemy_spawn(
+        mut q_enemy: Query<&mut Unit, Changed<Enemy>>,
+        q_rose: Query<&Transform, With<Rose>>,
+    ) {
+        let Ok(rose) = q_rose.get_single() else { return };
+
+        // Enemies from a save come back with their orders, only fresh ones head for the rose
         for mut enemy in &mut q_enemy {
-            enemy.command = Some(UnitCommand::AttackMove(Vec2::ZERO));
+            if enemy.command.is_none() {
+                enemy.command = Some(UnitCommand::AttackMove(rose.translation.truncate()));
+            }
         }
     }
 
+    // Orders go through the replay queue rather than straight to units, so they can be recorded
     fn process_command(
-        mut q_unit: Query<(&mut Unit, &Selectable)>,
+        rapier_ctx: Res<RapierContext>,
+        relations: Res<Relations>,
+        mut pending: ResMut<PendingActions>,
+        q_faction: Query<(&Faction, &SimId, Option<&Selectable>), Without<Dead>>,
         mouse_buttons: Res<Input<MouseButton>>,
         keyboard: Res<Input<KeyCode>>,
         mouse_pos: Res<MousePosition>,
     ) {
-        let mut command: Option<UnitCommand> = None;
+        let mut command: Option<ActionCommand> = None;
 
         if mouse_buttons.just_pressed(MouseButton::Right) {
-            command = Some(UnitCommand::Move(mouse_pos.truncate()));
+            let mut target = None;
+
+            rapier_ctx.intersections_with_point(mouse_pos.truncate(), QueryFilter::new(), |e| {
+                if let Ok((faction, id, _)) = q_faction.get(e) {
+                    if relations.get(Faction::Garden, *faction) != Relation::Allied {
+                        target = Some(*id);
+                        return false;
+                    }
+                }
+                true
+            });
+
+            command = Some(match target {
+                Some(target) => ActionCommand::Attack(target),
+                None => ActionCommand::Move(mouse_pos.truncate().into()),
+            });
         } else if keyboard.just_pressed(KeyCode::A) {
-            command = Some(UnitCommand::AttackMove(mouse_pos.truncate()));
+            command = Some(ActionCommand::AttackMove(mouse_pos.truncate().into()));
         }
-        if command.is_some() {
-            for (mut unit, selectable) in &mut q_unit {
-                if selectable.selected {
-                    unit.command = command.clone();
-                }
+        if let Some(command) = command {
+            let units = q_faction
+                .iter()
+                .filter(|(_, _, selectable)| selectable.is_some_and(|v| v.selected))
+                .map(|(_, id, _)| *id)
+                .collect::<Vec<_>>();
+
+            if !units.is_empty() {
+                pending.0.push(PlayerAction::Command { units, command });
             }
         }
     }
 
     fn crop_decay(
-        time: Res<Time>,
+        time: Res<SimTime>,
+        settings: Res<MatchSettings>,
         q_crop: Query<(Entity, &Crop)>,
         mut health_change: EventWriter<HealthChange>,
     ) {
         let delta = time.delta_seconds();
         for (target, crop) in &q_crop {
-            let amount = -delta
-                * match crop {
-                    Crop::Carrot => CARROT_HEALTH / (CARROT_DECAY_TIME + CARROT_GROW_TIME),
-                    Crop::Clover => CLOVER_HEALTH / (CLOVER_DECAY_TIME + CLOVER_GROW_TIME),
-                    Crop::Wheat => WHEAT_HEALTH / (WHEAT_DECAY_TIME + WHEAT_GROW_TIME),
-                };
-            health_change.send(HealthChange { target, amount });
+            let health = match crop {
+                Crop::Carrot => CARROT_HEALTH,
+                Crop::Clover => CLOVER_HEALTH,
+                Crop::Wheat => WHEAT_HEALTH,
+            };
+            let amount = -delta * health / (settings.decay_time(crop) + settings.grow_time(crop));
+            health_change.send(HealthChange {
+                target,
+                amount,
+                source: None,
+            });
         }
     }
 
@@ -747,13 +727,71 @@ impl Plugin {
 
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut App) {
-        app.add_system(Self::handle_harvest_event.run_in_state(GameState::InGame))
-            .add_system(Self::process_unit_state.run_in_state(GameState::InGame))
-            .add_system(Self::process_command.run_in_state(GameState::InGame))
-            .add_system(Self::enemy_spawn.run_in_state(GameState::InGame))
-            .add_system(Self::flip_unit.run_in_state(GameState::InGame))
-            .add_system(Self::crop_decay.run_in_state(GameState::InGame))
-            .add_system(Self::update_unit_state::<Friendly>.run_in_state(GameState::InGame))
-            .add_system(Self::update_unit_state::<Enemy>.run_in_state(GameState::InGame));
+        app.add_fixed_timestep_system(
+            SIM,
+            SIM_COMMANDS,
+            Self::handle_harvest_event
+                .run_in_state(GameState::InGame)
+                .after("plot_event"),
+        )
+        .add_fixed_timestep_system(
+            SIM,
+            SIM_MOVEMENT,
+            Self::move_units.run_in_state(GameState::InGame),
+        )
+        .add_fixed_timestep_system(
+            SIM,
+            SIM_COMBAT,
+            Self::attack_units.run_in_state(GameState::InGame),
+        )
+        .add_system_to_stage(
+            FrameStage::Input,
+            Self::process_command
+                .run_in_state(GameState::InGame)
+                .run_in_state(MatchState::Running)
+                .label("unit_command")
+                .after("selection"),
+        )
+        .add_fixed_timestep_system(
+            SIM,
+            SIM_AI,
+            Self::enemy_spawn.run_in_state(GameState::InGame),
+        )
+        .add_system(Self::flip_unit.run_in_state(GameState::InGame))
+        .add_fixed_timestep_system(
+            SIM,
+            SIM_COMBAT,
+            Self::crop_decay.run_in_state(GameState::InGame),
+        )
+        .add_fixed_timestep_system(
+            SIM,
+            SIM_AI,
+            Self::update_unit_state.run_in_state(GameState::InGame),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_enemies_head_for_the_rose_without_losing_orders() {
+        let mut app = App::new();
+        app.add_system(Plugin::enemy_spawn);
+
+        app.world
+            .spawn((Rose, Transform::from_xyz(40.0, 20.0, 0.0)));
+        let fresh = app.world.spawn((Unit::default(), Enemy)).id();
+        let mut unit = Unit::default();
+        unit.set_command(UnitCommand::Move(Vec2::new(-10.0, 0.0)));
+        let ordered = app.world.spawn((unit, Enemy)).id();
+
+        app.update();
+
+        let command = |entity| app.world.get::<Unit>(entity).unwrap().command().cloned();
+        let rose = Vec2::new(40.0, 20.0);
+        assert!(matches!(command(fresh), Some(UnitCommand::AttackMove(pos)) if pos == rose));
+        assert!(matches!(command(ordered), Some(UnitCommand::Move(pos)) if pos.x == -10.0));
     }
 }
@@ -1,11 +1,16 @@
-use std::time::Duration;
+use std::path::PathBuf;
 
-use bevy::prelude::*;
+use bevy::{asset::LoadState, prelude::*};
 use iyes_loopless::prelude::*;
 
 use crate::{
-    game::{Compost, Spawner},
+    consts::{DATA_DIR, SAVE_DIR},
     health::Dead,
+    level::Levels,
+    replay::ReplayMode,
+    scenario::Campaign,
+    sim::{SIM, SIM_DEATH},
+    waves::WaveDirector,
     GameState, MainCamera,
 };
 
@@ -19,10 +24,44 @@ pub struct Bar {
     pub size: f32,
 }
 
-pub struct Reset;
-
 pub struct PlaySound(pub String);
 
+// Where the game keeps files for the current user, e.g. `~/.local/share/harvrts` on Linux.
+// Platforms that don't say, like the web, get the working directory
+pub fn data_dir() -> PathBuf {
+    let var = |name| {
+        std::env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    let base = if cfg!(target_os = "windows") {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        var("XDG_DATA_HOME").or_else(|| var("HOME").map(|home| home.join(".local").join("share")))
+    };
+
+    base.map_or_else(PathBuf::new, |base| base.join(DATA_DIR))
+}
+
+pub fn save_dir() -> PathBuf {
+    data_dir().join(SAVE_DIR)
+}
+
+// Seconds since the Unix epoch, SystemTime panics on the web so that asks the browser
+#[cfg(not(target_arch = "wasm32"))]
+pub fn unix_seconds() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |v| v.as_secs())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn unix_seconds() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
 #[derive(Resource)]
 pub struct Preload(Vec<HandleUntyped>);
 
@@ -59,17 +98,10 @@ impl Plugin {
 
     fn reset(
         mut cmd: Commands,
-        mut time: ResMut<Time>,
+        mut director: ResMut<WaveDirector>,
         q_all: Query<Entity, (With<ComputedVisibility>, Without<Parent>)>,
     ) {
-        cmd.insert_resource(Spawner {
-            aphid: Timer::from_seconds(10.0, TimerMode::Repeating),
-            caterpillar: Timer::from_seconds(100.0, TimerMode::Repeating),
-            total: Duration::default(),
-        });
-        cmd.insert_resource(Compost(100));
-
-        time.set_relative_speed(1.0);
+        director.reset();
 
         for entity in &q_all {
             cmd.entity(entity).despawn_recursive();
@@ -86,6 +118,8 @@ impl Plugin {
         cmd.insert_resource(Preload(vec![
             assets.load_untyped("aphid.png"),
             assets.load_untyped("arrow.png"),
+            assets.load_untyped("bee.png"),
+            assets.load_untyped("butterfly.png"),
             assets.load_untyped("cancel.png"),
             assets.load_untyped("carrot_growing.png"),
             assets.load_untyped("carrot_grown.png"),
@@ -95,12 +129,17 @@ impl Plugin {
             assets.load_untyped("clover_growing.png"),
             assets.load_untyped("clover_grown.png"),
             assets.load_untyped("clover_unit.png"),
+            assets.load_untyped("cocoon.png"),
             assets.load_untyped("compost.ogg"),
             assets.load_untyped("compost.png"),
             assets.load_untyped("death_text.png"),
+            assets.load_untyped("egg.png"),
             assets.load_untyped("empty.png"),
+            assets.load_untyped("garden.waves.ron"),
             assets.load_untyped("harvest.png"),
+            assets.load_untyped("ladybug.png"),
             assets.load_untyped("menu_button.png"),
+            assets.load_untyped("nest.png"),
             assets.load_untyped("plant.ogg"),
             assets.load_untyped("plant_carrot.png"),
             assets.load_untyped("plant_clover.png"),
@@ -111,8 +150,8 @@ impl Plugin {
             assets.load_untyped("rocks.png"),
             assets.load_untyped("rose.png"),
             assets.load_untyped("snip.ogg"),
+            assets.load_untyped("sting.wav"),
             assets.load_untyped("title.png"),
-            assets.load_untyped("tutorial.png"),
             assets.load_untyped("wheat_growing.png"),
             assets.load_untyped("wheat_grown.png"),
             assets.load_untyped("wheat_unit.png"),
@@ -120,6 +159,30 @@ impl Plugin {
         ]));
     }
 
+    // Wave timing starts with the first tick, so a game can't start before the script is in.
+    // Replays go straight into their game, everything else starts at the main menu
+    fn finish_loading(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        director: Res<WaveDirector>,
+        levels: Option<Res<Levels>>,
+        campaign: Option<Res<Campaign>>,
+        mode: Option<Res<ReplayMode>>,
+    ) {
+        if levels.is_none()
+            || campaign.is_none()
+            || assets.get_load_state(&director.script) == LoadState::Loading
+        {
+            return;
+        }
+
+        if mode.is_some_and(|mode| mode.is_playback()) {
+            cmd.insert_resource(NextState(GameState::InGame));
+        } else {
+            cmd.insert_resource(NextState(GameState::MainMenu));
+        }
+    }
+
     fn in_game_clear_colour(mut clear_color: ResMut<ClearColor>) {
         clear_color.0 = Color::rgb_u8(17, 102, 0);
     }
@@ -128,14 +191,6 @@ impl Plugin {
         clear_color.0 = Color::rgb(0.4, 0.4, 0.4);
     }
 
-    fn pause_on_lost_focus(mut time: ResMut<Time>, windows: Res<Windows>) {
-        if windows.primary().is_focused() {
-            time.unpause();
-        } else {
-            time.pause();
-        }
-    }
-
     fn music(audio: Res<Audio>, assets: Res<AssetServer>) {
         audio.play_with_settings(
             assets.load("harvest-2-harvest-harder.ogg"),
@@ -149,15 +204,22 @@ impl bevy::app::Plugin for Plugin {
         app.add_startup_system(Self::preload)
             .add_startup_system(Self::music)
             .init_resource::<MousePosition>()
-            .init_resource::<Events<Reset>>()
             .add_event::<PlaySound>()
             .add_exit_system(GameState::InGame, Self::reset)
             .add_enter_system(GameState::InGame, Self::in_game_clear_colour)
+            .add_enter_system(GameState::Editor, Self::in_game_clear_colour)
             .add_enter_system(GameState::MainMenu, Self::main_menu_clear_colour)
-            .add_system(Self::pause_on_lost_focus)
+            .add_system(Self::finish_loading.run_in_state(GameState::Loading))
             .add_system(Self::update_bar.run_in_state(GameState::InGame))
             .add_system(Self::play_sound.run_in_state(GameState::InGame))
-            .add_system(Self::update_mouse_position.run_in_state(GameState::InGame))
-            .add_system(Self::handle_dead.run_in_state(GameState::InGame));
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                Self::update_mouse_position.run_not_in_state(GameState::MainMenu),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_DEATH,
+                Self::handle_dead.run_in_state(GameState::InGame),
+            );
     }
 }
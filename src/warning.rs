@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    consts::{EDGE_ARROW_MARGIN, SPAWN_WARNING_TIME},
+    enemy::{EnemyKind, Nest},
+    health::Dead,
+    pause::MatchState,
+    utils::PlaySound,
+    waves::IncomingEvent,
+    GameState, MainCamera,
+};
+
+// Points at a nest that's about to spawn, from the edge of the screen if it's out of view
+#[derive(Component)]
+pub struct EdgeArrow {
+    nest: Entity,
+    kind: EnemyKind,
+    timer: Timer,
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn handle_incoming(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        mut events: EventReader<IncomingEvent>,
+        mut ev_sound: EventWriter<PlaySound>,
+        mut q_arrow: Query<&mut EdgeArrow>,
+    ) {
+        for event in events.iter() {
+            if event.kind == EnemyKind::Caterpillar {
+                ev_sound.send(PlaySound("sting.wav".to_string()));
+            }
+
+            let timer = Timer::from_seconds(SPAWN_WARNING_TIME, TimerMode::Once);
+
+            // Keep one arrow per nest and kind, a fresh warning just extends it
+            if let Some(mut arrow) = q_arrow
+                .iter_mut()
+                .find(|arrow| arrow.nest == event.nest && arrow.kind == event.kind)
+            {
+                arrow.timer = timer;
+                continue;
+            }
+
+            cmd.spawn((
+                SpriteBundle {
+                    texture: assets.load("arrow.png"),
+                    sprite: Sprite {
+                        color: event.kind.nest_colour(),
+                        ..default()
+                    },
+                    visibility: Visibility::INVISIBLE,
+                    ..default()
+                },
+                EdgeArrow {
+                    nest: event.nest,
+                    kind: event.kind,
+                    timer,
+                },
+            ));
+        }
+    }
+
+    fn update_arrows(
+        mut cmd: Commands,
+        time: Res<Time>,
+        q_camera: Query<(&OrthographicProjection, &GlobalTransform), With<MainCamera>>,
+        q_nest: Query<&GlobalTransform, (With<Nest>, Without<Dead>)>,
+        mut q_arrow: Query<(
+            Entity,
+            &mut EdgeArrow,
+            &mut Transform,
+            &mut Sprite,
+            &mut Visibility,
+        )>,
+    ) {
+        let Ok((projection, camera_transform)) = q_camera.get_single() else { return };
+
+        let centre = camera_transform.translation().truncate();
+        let half_size = Vec2::new(
+            projection.right - projection.left,
+            projection.top - projection.bottom,
+        ) * projection.scale
+            / 2.0
+            - Vec2::splat(EDGE_ARROW_MARGIN);
+
+        for (entity, mut arrow, mut transform, mut sprite, mut visibility) in &mut q_arrow {
+            arrow.timer.tick(time.delta());
+
+            let Ok(nest_transform) = q_nest.get(arrow.nest) else {
+                cmd.entity(entity).despawn_recursive();
+                continue;
+            };
+            if arrow.timer.finished() {
+                cmd.entity(entity).despawn_recursive();
+                continue;
+            }
+
+            let target = nest_transform.translation().truncate();
+            let clamped = target.clamp(centre - half_size, centre + half_size);
+
+            // The arrow image points down, on screen it just hovers over the nest
+            let (pos, direction) = if clamped == target {
+                (target + Vec2::new(0.0, EDGE_ARROW_MARGIN), Vec2::NEG_Y)
+            } else {
+                (clamped, target - clamped)
+            };
+
+            transform.translation = pos.extend(2.0);
+            transform.rotation = Quat::from_rotation_z(Vec2::NEG_Y.angle_between(direction));
+            sprite
+                .color
+                .set_a(0.6 + 0.4 * (arrow.timer.elapsed_secs() * 12.0).sin());
+            visibility.is_visible = true;
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            Self::handle_incoming
+                .run_in_state(GameState::InGame)
+                .label("incoming_arrows"),
+        )
+        .add_system(
+            Self::update_arrows
+                .run_in_state(GameState::InGame)
+                .run_in_state(MatchState::Running)
+                .after("incoming_arrows"),
+        );
+    }
+}
@@ -0,0 +1,470 @@
+use std::time::Duration;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    challenge::ChallengeChoice,
+    consts::*,
+    difficulty::Difficulty,
+    enemy::{spawn_enemy, EnemyKind, Nest},
+    game_menu::WaveText,
+    health::{Dead, Health, HealthBar},
+    match_settings::MatchSettings,
+    rng::{GameRng, RngStream},
+    sim::{SimTime, SIM, SIM_AI, SIM_COMMANDS},
+    tutorial::waves_held,
+    unit::Unit,
+    utils::Bar,
+    GameState,
+};
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "8a3c6f0e-4b1d-4f7a-9c2e-5d6b7e8f9a01"]
+pub struct WaveScript {
+    // Once the scripted waves run out the director loops back to this one
+    pub repeat_from: usize,
+    // The match is won once this wave has spawned and the garden is clear of pests
+    #[serde(default)]
+    pub victory_wave: Option<u32>,
+    pub curve: DifficultyCurve,
+    pub waves: Vec<Wave>,
+}
+
+// Growth per wave, so wave n gets 1.0 + n * value on top of its own modifiers
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(default)]
+pub struct DifficultyCurve {
+    pub count: f32,
+    pub health: f32,
+    pub speed: f32,
+    pub damage: f32,
+}
+
+#[derive(Deserialize)]
+pub struct Wave {
+    pub intermission: f32,
+    pub groups: Vec<SpawnGroup>,
+    #[serde(default)]
+    pub modifiers: WaveModifiers,
+}
+
+#[derive(Deserialize)]
+pub struct SpawnGroup {
+    pub kind: EnemyKind,
+    pub count: u32,
+    #[serde(default)]
+    pub delay: f32,
+    pub interval: f32,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct WaveModifiers {
+    pub health: f32,
+    pub speed: f32,
+    pub damage: f32,
+}
+
+impl Default for WaveModifiers {
+    fn default() -> Self {
+        WaveModifiers {
+            health: 1.0,
+            speed: 1.0,
+            damage: 1.0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct WaveScriptLoader;
+
+impl AssetLoader for WaveScriptLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let script = ron::de::from_bytes::<WaveScript>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(script));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ActiveGroup {
+    kind: EnemyKind,
+    remaining: u32,
+    timer: Timer,
+    interval: f32,
+    // Whether the next spawn has been announced yet
+    #[serde(default)]
+    warned: bool,
+    // Nest position and offset of the announced spawn
+    #[serde(default)]
+    spawn: Option<(Vec2, Vec2)>,
+}
+
+// Sent a little before a group spawns, so the player can see it coming
+pub struct IncomingEvent {
+    pub kind: EnemyKind,
+    pub nest: Entity,
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct WaveDirector {
+    #[serde(skip)]
+    pub script: Handle<WaveScript>,
+    // Number of the current (or upcoming, during an intermission) wave, starting at 1
+    pub wave: u32,
+    pub total: Duration,
+    // Set once the victory wave has finished spawning, no more waves follow
+    #[serde(default)]
+    pub finished: bool,
+    intermission: Option<Timer>,
+    groups: Vec<ActiveGroup>,
+    modifiers: WaveModifiers,
+}
+
+impl FromWorld for WaveDirector {
+    fn from_world(world: &mut World) -> Self {
+        let assets = world.resource::<AssetServer>();
+        WaveDirector::new(assets.load(WAVE_SCRIPT))
+    }
+}
+
+impl WaveDirector {
+    pub fn new(script: Handle<WaveScript>) -> Self {
+        WaveDirector {
+            script,
+            wave: 0,
+            total: Duration::default(),
+            finished: false,
+            intermission: None,
+            groups: Vec::new(),
+            modifiers: WaveModifiers::default(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = WaveDirector::new(self.script.clone());
+    }
+
+    // Picks up the progress of a saved director, the script isn't part of saves
+    pub fn restore(&mut self, saved: WaveDirector) {
+        *self = WaveDirector {
+            script: self.script.clone(),
+            ..saved
+        };
+    }
+
+    pub fn intermission_left(&self) -> Option<f32> {
+        self.intermission.as_ref().map(|v| v.remaining_secs())
+    }
+
+    fn queue_next_wave(&mut self, script: &WaveScript, spawn_rate: f32) {
+        self.wave += 1;
+        let wave = Self::scripted_wave(script, self.wave);
+        self.intermission = Some(Timer::from_seconds(
+            wave.intermission / spawn_rate,
+            TimerMode::Once,
+        ));
+    }
+
+    fn start_wave(
+        &mut self,
+        script: &WaveScript,
+        spawn_rate: f32,
+        challenge: &ChallengeChoice,
+        settings: &MatchSettings,
+    ) {
+        let wave = Self::scripted_wave(script, self.wave);
+        let curve = script.curve;
+        let step = (self.wave - 1) as f32;
+
+        self.modifiers = WaveModifiers {
+            health: wave.modifiers.health * (1.0 + curve.health * step),
+            speed: wave.modifiers.speed * (1.0 + curve.speed * step),
+            damage: wave.modifiers.damage * (1.0 + curve.damage * step),
+        };
+
+        self.groups = wave
+            .groups
+            .iter()
+            .map(|group| ActiveGroup {
+                kind: group.kind,
+                remaining: (group.count as f32
+                    * (1.0 + curve.count * step)
+                    * challenge.enemy_count(group.kind))
+                .round() as u32,
+                timer: Timer::from_seconds(group.delay / spawn_rate, TimerMode::Once),
+                interval: group.interval / spawn_rate * settings.spawn_interval(group.kind),
+                warned: false,
+                spawn: None,
+            })
+            // Small counts can scale down to nothing, and spawning counts down from at least one
+            .filter(|group| group.remaining > 0)
+            .collect();
+    }
+
+    fn scripted_wave(script: &WaveScript, wave: u32) -> &Wave {
+        let index = wave as usize - 1;
+        let len = script.waves.len();
+
+        if index < len {
+            &script.waves[index]
+        } else {
+            let repeat_from = script.repeat_from.min(len - 1);
+            &script.waves[repeat_from + (index - len) % (len - repeat_from)]
+        }
+    }
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn direct_waves(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        scripts: Res<Assets<WaveScript>>,
+        mut director: ResMut<WaveDirector>,
+        (difficulty, challenge, settings): (
+            Res<Difficulty>,
+            Res<ChallengeChoice>,
+            Res<MatchSettings>,
+        ),
+        (rng, time): (Res<GameRng>, Res<SimTime>),
+        mut ev_incoming: EventWriter<IncomingEvent>,
+        q_nest: Query<(Entity, &Nest, &GlobalTransform), Without<Dead>>,
+    ) {
+        let Some(script) = scripts.get(&director.script) else { return };
+        director.total += time.delta();
+
+        if script.waves.is_empty() {
+            return;
+        }
+
+        if let Some(intermission) = &mut director.intermission {
+            intermission.tick(time.delta());
+
+            if intermission.finished() {
+                director.intermission = None;
+                director.start_wave(script, difficulty.spawn_rate(), &challenge, &settings);
+            }
+            return;
+        }
+
+        if director.finished {
+            return;
+        }
+
+        if director.groups.is_empty() {
+            if script
+                .victory_wave
+                .is_some_and(|wave| director.wave >= wave)
+            {
+                director.finished = true;
+                return;
+            }
+
+            director.queue_next_wave(script, difficulty.spawn_rate());
+            return;
+        }
+
+        let rng = rng.stream(RngStream::Waves);
+        let modifiers = director.modifiers;
+
+        for group in &mut director.groups {
+            group.timer.tick(time.delta());
+
+            let nests = q_nest
+                .iter()
+                .filter(|(_, nest, _)| nest.kind == group.kind.nest_kind())
+                .map(|(entity, _, transform)| (entity, transform.translation().truncate()))
+                .collect::<Vec<_>>();
+
+            // The spawn point is picked along with the warning, so the arrow shows where it'll be
+            if !group.warned && group.timer.remaining_secs() <= SPAWN_WARNING_TIME {
+                group.warned = true;
+
+                if !nests.is_empty() {
+                    let (nest, pos, offset) = Self::spawn_point(&rng, &nests);
+                    group.spawn = Some((pos, offset));
+                    ev_incoming.send(IncomingEvent {
+                        kind: group.kind,
+                        nest,
+                    });
+                }
+            }
+
+            if !group.timer.just_finished() {
+                continue;
+            }
+
+            group.remaining -= 1;
+            group.timer = Timer::from_seconds(group.interval, TimerMode::Once);
+            group.warned = false;
+            let spawn = group.spawn.take();
+
+            // Groups whose nests are all destroyed fizzle out instead of spawning
+            if nests.is_empty() {
+                continue;
+            }
+
+            // Another nest takes over if the announced one was destroyed in the meantime
+            let (pos, offset) = spawn
+                .filter(|(pos, _)| nests.iter().any(|(_, nest)| nest == pos))
+                .unwrap_or_else(|| {
+                    let (_, pos, offset) = Self::spawn_point(&rng, &nests);
+                    (pos, offset)
+                });
+
+            let enemy = spawn_enemy(&mut cmd, &assets, group.kind, pos + offset);
+            cmd.entity(enemy).insert(modifiers);
+        }
+
+        director.groups.retain(|group| group.remaining > 0);
+    }
+
+    fn spawn_point(rng: &fastrand::Rng, nests: &[(Entity, Vec2)]) -> (Entity, Vec2, Vec2) {
+        let (nest, pos) = nests[rng.usize(..nests.len())];
+        let offset = Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5) * NEST_SPAWN_SPREAD;
+        (nest, pos, offset)
+    }
+
+    fn apply_modifiers(
+        mut q_enemy: Query<
+            (&WaveModifiers, &mut Unit, &mut Health, &Children),
+            Added<WaveModifiers>,
+        >,
+        mut q_healthbar: Query<&mut Bar, With<HealthBar>>,
+        difficulty: Res<Difficulty>,
+    ) {
+        for (modifiers, mut unit, mut health, children) in &mut q_enemy {
+            let health_scale = modifiers.health * difficulty.enemy_health();
+            unit.scale(
+                modifiers.speed,
+                modifiers.damage * difficulty.enemy_damage(),
+            );
+            health.scale(health_scale);
+
+            for child in children.iter() {
+                if let Ok(mut bar) = q_healthbar.get_mut(*child) {
+                    bar.max *= health_scale;
+                }
+            }
+        }
+    }
+
+    fn update_wave_text(director: Res<WaveDirector>, mut q_text: Query<&mut Text, With<WaveText>>) {
+        let mut text = q_text.single_mut();
+
+        text.sections[0].value = match director.intermission_left() {
+            Some(seconds) => format!("Wave {} in {}", director.wave, seconds.ceil()),
+            None if director.wave == 0 => String::new(),
+            None if director.finished => "Final wave".to_string(),
+            None => format!("Wave {}", director.wave),
+        };
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<WaveScript>()
+            .init_asset_loader::<WaveScriptLoader>()
+            .init_resource::<WaveDirector>()
+            .add_event::<IncomingEvent>()
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_COMMANDS,
+                Self::direct_waves
+                    .run_in_state(GameState::InGame)
+                    .run_if_not(waves_held),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_AI,
+                Self::apply_modifiers.run_in_state(GameState::InGame),
+            )
+            .add_system(Self::update_wave_text.run_in_state(GameState::InGame));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Four waves, told apart by how many aphids their only group spawns
+    fn script(repeat_from: usize) -> WaveScript {
+        let waves = (1..=4)
+            .map(|count| {
+                format!(
+                    "(intermission: 0.0, groups: [(kind: Aphid, count: {}, interval: 1.0)])",
+                    count
+                )
+            })
+            .collect::<Vec<_>>();
+
+        ron::from_str(&format!(
+            "(repeat_from: {}, curve: (count: 0.0, health: 0.0, speed: 0.0, damage: 0.0), waves: [{}])",
+            repeat_from,
+            waves.join(", ")
+        ))
+        .unwrap()
+    }
+
+    fn counts(script: &WaveScript, waves: std::ops::RangeInclusive<u32>) -> Vec<u32> {
+        waves
+            .map(|wave| WaveDirector::scripted_wave(script, wave).groups[0].count)
+            .collect()
+    }
+
+    #[test]
+    fn waves_loop_back_to_repeat_from_once_the_script_runs_out() {
+        assert_eq!(counts(&script(1), 1..=10), [1, 2, 3, 4, 2, 3, 4, 2, 3, 4]);
+        assert_eq!(counts(&script(0), 1..=6), [1, 2, 3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn repeat_from_past_the_end_keeps_playing_the_last_wave() {
+        assert_eq!(counts(&script(10), 3..=7), [3, 4, 4, 4, 4]);
+    }
+
+    #[test]
+    fn groups_that_scale_down_to_nothing_are_dropped() {
+        let mut script = script(0);
+        // Shrinks the second wave's groups to a quarter
+        script.curve.count = -0.75;
+        script.waves[1].groups.push(SpawnGroup {
+            kind: EnemyKind::Caterpillar,
+            count: 8,
+            delay: 0.0,
+            interval: 1.0,
+        });
+
+        let mut director = WaveDirector::new(Handle::default());
+        director.wave = 2;
+        director.start_wave(
+            &script,
+            1.0,
+            &ChallengeChoice::default(),
+            &MatchSettings::default(),
+        );
+
+        assert_eq!(director.groups.len(), 1);
+        assert_eq!(director.groups[0].kind, EnemyKind::Caterpillar);
+        assert_eq!(director.groups[0].remaining, 2);
+    }
+}
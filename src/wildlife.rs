@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::*,
+    faction::Faction,
+    health::{Dead, Health, HealthBar},
+    rng::{GameRng, RngStream},
+    sim::{SimTime, SIM, SIM_COMMANDS},
+    unit::{Unit, UnitCommand},
+    utils::Bar,
+    GameState,
+};
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WildlifeKind {
+    Ladybug,
+    Bee,
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Wander {
+    home: Vec2,
+    radius: f32,
+    timer: Timer,
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct WildlifeSpawner(Timer);
+
+impl Default for WildlifeSpawner {
+    fn default() -> Self {
+        WildlifeSpawner(Timer::from_seconds(
+            WILDLIFE_SPAWN_TIME,
+            TimerMode::Repeating,
+        ))
+    }
+}
+
+pub fn spawn_wildlife(
+    cmd: &mut Commands,
+    assets: &AssetServer,
+    kind: WildlifeKind,
+    pos: Vec2,
+) -> Entity {
+    let (faction, texture, unit, health, radius) = match kind {
+        WildlifeKind::Ladybug => (
+            Faction::Predators,
+            "ladybug.png",
+            Unit::new(
+                LADYBUG_MOVE_SPEED,
+                LADYBUG_AGGRO_RANGE,
+                LADYBUG_CHASE_RANGE,
+                LADYBUG_ATTACK_RANGE,
+                LADYBUG_LEASH_RANGE,
+                LADYBUG_ATTACK_SPEED,
+                LADYBUG_DAMAGE,
+            ),
+            LADYBUG_HEALTH,
+            LADYBUG_WANDER_RADIUS,
+        ),
+        WildlifeKind::Bee => (
+            Faction::Wildlife,
+            "bee.png",
+            Unit::new(
+                BEE_MOVE_SPEED,
+                BEE_AGGRO_RANGE,
+                BEE_CHASE_RANGE,
+                BEE_ATTACK_RANGE,
+                BEE_LEASH_RANGE,
+                BEE_ATTACK_SPEED,
+                BEE_DAMAGE,
+            ),
+            BEE_HEALTH,
+            BEE_WANDER_RADIUS,
+        ),
+    };
+
+    cmd.spawn((
+        SpriteBundle {
+            texture: assets.load(texture),
+            transform: Transform::from_translation(pos.extend(0.1)),
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Velocity::default(),
+        Collider::ball(4.0),
+        LockedAxes::ROTATION_LOCKED_Z,
+        CollisionGroups {
+            memberships: UNIT_COLLISION_GROUP | faction.group(),
+            filters: UNIT_COLLISION_GROUP,
+        },
+        Damping {
+            linear_damping: 20.0,
+            angular_damping: 0.0,
+        },
+        unit,
+        Health::new(health),
+        faction,
+        kind,
+        Wander {
+            home: pos,
+            radius,
+            timer: Timer::from_seconds(WANDER_TIME, TimerMode::Repeating),
+        },
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            SpriteBundle {
+                transform: Transform::from_translation(Vec3::new(0.0, -4.0, 0.1)),
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(2.0, 1.0)),
+                    color: Color::RED,
+                    ..default()
+                },
+                ..default()
+            },
+            Bar {
+                value: health,
+                max: health,
+                size: 10.0,
+            },
+            HealthBar,
+        ));
+    })
+    .id()
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn init(mut cmd: Commands, assets: Res<AssetServer>, rng: Res<GameRng>) {
+        let rng = rng.stream(RngStream::Wildlife);
+
+        for (kind, count) in [
+            (WildlifeKind::Ladybug, LADYBUG_COUNT),
+            (WildlifeKind::Bee, BEE_COUNT),
+        ] {
+            for _ in 0..count {
+                let pos = Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5) * WILDLIFE_AREA;
+                spawn_wildlife(&mut cmd, &assets, kind, pos);
+            }
+        }
+
+        cmd.insert_resource(WildlifeSpawner::default());
+    }
+
+    fn wildlife_spawning(
+        mut cmd: Commands,
+        assets: Res<AssetServer>,
+        time: Res<SimTime>,
+        mut spawner: ResMut<WildlifeSpawner>,
+        rng: Res<GameRng>,
+        q_wildlife: Query<&WildlifeKind, Without<Dead>>,
+    ) {
+        spawner.0.tick(time.delta());
+
+        if !spawner.0.just_finished() {
+            return;
+        }
+
+        let rng = rng.stream(RngStream::Wildlife);
+
+        for (kind, count) in [
+            (WildlifeKind::Ladybug, LADYBUG_COUNT),
+            (WildlifeKind::Bee, BEE_COUNT),
+        ] {
+            let alive = q_wildlife.iter().filter(|v| **v == kind).count();
+
+            for _ in alive as u32..count {
+                let pos = Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5) * WILDLIFE_AREA;
+                spawn_wildlife(&mut cmd, &assets, kind, pos);
+            }
+        }
+    }
+
+    fn wander(
+        mut q_wander: Query<(&mut Wander, &mut Unit)>,
+        time: Res<SimTime>,
+        rng: Res<GameRng>,
+    ) {
+        let rng = rng.stream(RngStream::Wander);
+
+        for (mut wander, mut unit) in &mut q_wander {
+            wander.timer.tick(time.delta());
+
+            // Wildlife that's still on its way or in a fight carries on with that first
+            if wander.timer.just_finished() && unit.is_idle() {
+                let offset = Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5) * 2.0 * wander.radius;
+                unit.set_command(UnitCommand::AttackMove(wander.home + offset));
+            }
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_enter_system(GameState::InGame, Self::init)
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_COMMANDS,
+                Self::wildlife_spawning.run_in_state(GameState::InGame),
+            )
+            .add_fixed_timestep_system(
+                SIM,
+                SIM_COMMANDS,
+                Self::wander.run_in_state(GameState::InGame),
+            );
+    }
+}